@@ -8,7 +8,7 @@ use std::{env, path::PathBuf};
 fn main() {
     // GL Generator
     let dest = env::var("OUT_DIR").unwrap();
-    let mut file = File::create(&Path::new(&dest).join("bindings.rs")).unwrap();
+    let mut file = File::create(Path::new(&dest).join("bindings.rs")).unwrap();
 
     Registry::new(Api::Gl, (4, 3), Profile::Core, Fallbacks::All, [])
         .write_bindings(StructGenerator, &mut file)
@@ -0,0 +1,12 @@
+#version 300 es
+
+precision mediump float;
+
+in vec4 v_color;
+
+out vec4 out_color;
+
+void main()
+{
+    out_color = v_color;
+}
@@ -0,0 +1,20 @@
+#version 300 es
+
+layout(location=0) in vec4 position; //will be compatible with vec2 and vec3 attributes. extended to vec4: (0, 0, 0, 1) for missing components
+layout(location=1) in vec4 color;
+
+layout(std140) uniform Camera
+{
+    mat4 view_projection;
+};
+
+uniform mat4 model;
+uniform vec4 tint;
+
+out vec4 v_color;
+
+void main()
+{
+    gl_Position = view_projection * model * position;
+    v_color = color * tint;
+}
@@ -1,3 +1,4 @@
+use crate::gl;
 use crate::scene;
 use crate::window;
 use sdl2::event::Event;
@@ -6,8 +7,15 @@ use sdl2::keyboard::Scancode;
 pub struct Game {
     scene: scene::Scene,
     window: window::GlWindow,
+    /// Only present in debug builds, where shaders are reloaded from disk when they change
+    shader_watcher: Option<gl::ShaderWatcher>,
+    /// Set when the screenshot key is pressed, the screenshot is taken once the frame is rendered
+    screenshot_requested: bool,
 }
 
+/// Where screenshots taken with F12 are written
+const SCREENSHOT_DIRECTORY: &str = "screenshots";
+
 impl Game {
     pub fn new() -> Result<Self, anyhow::Error> {
         // Load window title from file just to show that it works to load files in the Emscripten builds
@@ -19,7 +27,21 @@ impl Game {
         // Pass in an Rc of Gl to the scene so that it can create the Gl entities required
         let scene = scene::Scene::new(window.gl.clone(), size);
 
-        Ok(Self { window, scene })
+        let shader_watcher = if cfg!(debug_assertions) {
+            Some(gl::ShaderWatcher::new(
+                gl::default_shader_builder(),
+                window.gl.clone(),
+            ))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            window,
+            scene,
+            shader_watcher,
+            screenshot_requested: false,
+        })
     }
 }
 
@@ -44,16 +66,42 @@ impl emscripten_main_loop::MainLoop for Game {
                     scancode: Some(Scancode::Space),
                     ..
                 } => self.scene.randomize(),
+                // Save a screenshot of the next frame if the user presses F12
+                Event::KeyDown {
+                    scancode: Some(Scancode::F12),
+                    ..
+                } => self.screenshot_requested = true,
                 _ => {}
             }
         }
 
+        // Pick up any shader changes made on disk
+        if let Some(shader_watcher) = &mut self.shader_watcher {
+            if shader_watcher.poll(self.scene.shader_mut()) {
+                if let Err(e) = self.scene.on_shader_reloaded(&self.window.gl) {
+                    // The new program is already in use, so it renders without camera data
+                    eprintln!("Reloaded shader could not be set up: {}", e);
+                }
+            }
+        }
+
         // Advance the logic of the scene one frame
         self.scene.update();
 
         // Render a single frame
         self.scene.render(&self.window.gl);
 
+        // Read back the frame before it is swapped away
+        if self.screenshot_requested {
+            self.screenshot_requested = false;
+            let (width, height) = self.window.window.drawable_size();
+            let screenshot = gl::read_window_pixels(glm::vec2(width, height), &self.window.gl);
+            match screenshot.save_timestamped_png(SCREENSHOT_DIRECTORY) {
+                Ok(path) => println!("Saved screenshot to {}", path.display()),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+
         // Display the rendered frame on the window
         self.window.window.gl_swap_window();
 
@@ -0,0 +1,423 @@
+use super::*;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum AtlasError {
+    /// The image plus padding does not fit on an empty page
+    ImageTooLarge {
+        name: String,
+        size: glm::UVec2,
+        page_size: glm::UVec2,
+    },
+    DuplicateName(String),
+    FormatMismatch {
+        name: String,
+        format: TextureFormat,
+        atlas_format: TextureFormat,
+    },
+    Texture(TextureError),
+}
+
+impl std::error::Error for AtlasError {}
+impl std::fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let to_write = match self {
+            AtlasError::ImageTooLarge {
+                name,
+                size,
+                page_size,
+            } => std::format!(
+                "Image '{}' of size {}x{} does not fit in atlas pages of size {}x{}",
+                name,
+                size.x,
+                size.y,
+                page_size.x,
+                page_size.y
+            ),
+            AtlasError::DuplicateName(name) => {
+                std::format!("Atlas already contains an image named '{}'", name)
+            }
+            AtlasError::FormatMismatch {
+                name,
+                format,
+                atlas_format,
+            } => std::format!(
+                "Image '{}' has format {:?} but the atlas stores {:?}",
+                name,
+                format,
+                atlas_format
+            ),
+            AtlasError::Texture(e) => std::format!("Could not update atlas texture: {}", e),
+        };
+        write!(f, "{}", to_write)
+    }
+}
+
+impl From<TextureError> for AtlasError {
+    fn from(e: TextureError) -> Self {
+        AtlasError::Texture(e)
+    }
+}
+
+/// Where an image ended up in a `TextureAtlas`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub struct AtlasRegion {
+    /// Index of the page texture, see `TextureAtlas::page_texture`
+    pub page: usize,
+    /// Position of the image in pixels, excluding padding
+    pub position: glm::UVec2,
+    pub size: glm::UVec2,
+    /// Texture coordinates of the corners of the image. The first row of the image data is at
+    /// `uv_min.y`
+    pub uv_min: glm::Vec2,
+    pub uv_max: glm::Vec2,
+}
+
+/// Packs rectangles into rows of increasing y. Each row is as tall as the first rectangle placed
+/// in it, which works well when images of similar height are added together
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct ShelfPacker {
+    size: glm::UVec2,
+    shelves: Vec<Shelf>,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[allow(dead_code)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    /// Where the next rectangle on this shelf goes
+    x: u32,
+}
+
+#[allow(dead_code)]
+impl ShelfPacker {
+    pub fn new(size: glm::UVec2) -> Self {
+        Self {
+            size,
+            shelves: Vec::new(),
+        }
+    }
+    pub fn size(&self) -> glm::UVec2 {
+        self.size
+    }
+    /// Returns the position of the rectangle, or None if there is no room left for it
+    pub fn pack(&mut self, size: glm::UVec2) -> Option<glm::UVec2> {
+        if size.x > self.size.x || size.y > self.size.y {
+            return None;
+        }
+
+        // Use the shelf that wastes the least height
+        let width = self.size.x;
+        let best_shelf = self
+            .shelves
+            .iter_mut()
+            .filter(|shelf| shelf.height >= size.y && width - shelf.x >= size.x)
+            .min_by_key(|shelf| shelf.height - size.y);
+        if let Some(shelf) = best_shelf {
+            let position = glm::vec2(shelf.x, shelf.y);
+            shelf.x += size.x;
+            return Some(position);
+        }
+
+        let y = self
+            .shelves
+            .last()
+            .map(|shelf| shelf.y + shelf.height)
+            .unwrap_or(0);
+        if self.size.y - y < size.y {
+            return None;
+        }
+        self.shelves.push(Shelf {
+            y,
+            height: size.y,
+            x: size.x,
+        });
+        Some(glm::vec2(0, y))
+    }
+    /// Height covered by shelves, including the unused space at their ends
+    pub fn used_height(&self) -> u32 {
+        self.shelves
+            .last()
+            .map(|shelf| shelf.y + shelf.height)
+            .unwrap_or(0)
+    }
+}
+
+#[allow(dead_code)]
+struct AtlasPage {
+    texture: Texture2D,
+    packer: ShelfPacker,
+}
+
+/// Collects many small images into a few large textures so that they can be drawn without
+/// switching textures. New pages are created when the existing ones are full
+#[allow(dead_code)]
+pub struct TextureAtlas {
+    page_size: glm::UVec2,
+    format: TextureFormat,
+    /// Pixels around every image filled with copies of its edge, so that linear filtering near
+    /// the edge does not pick up neighbouring images
+    padding: u32,
+    pages: Vec<AtlasPage>,
+    regions: HashMap<String, AtlasRegion>,
+    /// Names in the order they were added, for `dump_layout`
+    names: Vec<String>,
+    gl: std::rc::Rc<Gl>,
+}
+
+#[allow(dead_code)]
+impl TextureAtlas {
+    pub fn new(
+        page_size: glm::UVec2,
+        format: TextureFormat,
+        padding: u32,
+        gl: std::rc::Rc<Gl>,
+    ) -> Self {
+        Self {
+            page_size,
+            format,
+            padding,
+            pages: Vec::new(),
+            regions: HashMap::new(),
+            names: Vec::new(),
+            gl,
+        }
+    }
+    pub fn page_size(&self) -> glm::UVec2 {
+        self.page_size
+    }
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+    pub fn page_texture(&self, page: usize) -> Option<&Texture2D> {
+        self.pages.get(page).map(|page| &page.texture)
+    }
+    pub fn region(&self, name: &str) -> Option<&AtlasRegion> {
+        self.regions.get(name)
+    }
+    /// Packs an image with tightly packed rows in the format of the atlas and uploads it
+    pub fn add(
+        &mut self,
+        name: &str,
+        size: glm::UVec2,
+        data: &[u8],
+    ) -> Result<AtlasRegion, AtlasError> {
+        if self.regions.contains_key(name) {
+            return Err(AtlasError::DuplicateName(name.to_owned()));
+        }
+        let bytes_per_pixel = self.format.bytes_per_pixel();
+        let expected = (size.x * size.y) as usize * bytes_per_pixel;
+        if data.len() != expected {
+            return Err(AtlasError::Texture(TextureError::DataSizeMismatch {
+                expected,
+                actual: data.len(),
+            }));
+        }
+
+        let padded_size = size + glm::vec2(self.padding * 2, self.padding * 2);
+        let (page, padded_position) = self.allocate(name, size, padded_size)?;
+
+        let padded_data = extrude(data, size, self.padding, bytes_per_pixel);
+        self.pages[page].texture.upload_sub_image(
+            padded_position,
+            padded_size,
+            &padded_data,
+            &self.gl,
+        )?;
+
+        let position = padded_position + glm::vec2(self.padding, self.padding);
+        let page_size = glm::vec2(self.page_size.x as f32, self.page_size.y as f32);
+        let region = AtlasRegion {
+            page,
+            position,
+            size,
+            uv_min: glm::vec2(position.x as f32, position.y as f32).component_div(&page_size),
+            uv_max: glm::vec2((position.x + size.x) as f32, (position.y + size.y) as f32)
+                .component_div(&page_size),
+        };
+        self.regions.insert(name.to_owned(), region);
+        self.names.push(name.to_owned());
+        Ok(region)
+    }
+    /// Packs a decoded image file, which has to be in the format of the atlas
+    pub fn add_image(
+        &mut self,
+        name: &str,
+        image: &DecodedImage,
+    ) -> Result<AtlasRegion, AtlasError> {
+        if image.format != self.format {
+            return Err(AtlasError::FormatMismatch {
+                name: name.to_owned(),
+                format: image.format,
+                atlas_format: self.format,
+            });
+        }
+        self.add(name, image.size, &image.data)
+    }
+    /// Describes every page and the regions on it, to be printed when debugging
+    pub fn dump_layout(&self) -> String {
+        let mut res = String::new();
+        for (page_index, page) in self.pages.iter().enumerate() {
+            // Writing to a String cannot fail
+            writeln!(
+                res,
+                "Page {} ({}x{} {:?}, {} of {} rows used):",
+                page_index,
+                self.page_size.x,
+                self.page_size.y,
+                self.format,
+                page.packer.used_height(),
+                self.page_size.y
+            )
+            .unwrap();
+            for name in &self.names {
+                let region = &self.regions[name];
+                if region.page != page_index {
+                    continue;
+                }
+                writeln!(
+                    res,
+                    "  {}: {}x{} at ({}, {}), uv ({:.4}, {:.4})-({:.4}, {:.4})",
+                    name,
+                    region.size.x,
+                    region.size.y,
+                    region.position.x,
+                    region.position.y,
+                    region.uv_min.x,
+                    region.uv_min.y,
+                    region.uv_max.x,
+                    region.uv_max.y
+                )
+                .unwrap();
+            }
+        }
+        res
+    }
+
+    /// Finds room on an existing page or starts a new one. Returns the page and the position of
+    /// the padded rectangle
+    fn allocate(
+        &mut self,
+        name: &str,
+        size: glm::UVec2,
+        padded_size: glm::UVec2,
+    ) -> Result<(usize, glm::UVec2), AtlasError> {
+        for (page_index, page) in self.pages.iter_mut().enumerate() {
+            if let Some(position) = page.packer.pack(padded_size) {
+                return Ok((page_index, position));
+            }
+        }
+
+        let mut packer = ShelfPacker::new(self.page_size);
+        let position = packer
+            .pack(padded_size)
+            .ok_or_else(|| AtlasError::ImageTooLarge {
+                name: name.to_owned(),
+                size,
+                page_size: self.page_size,
+            })?;
+
+        // Cleared so that unused space does not show garbage when the page is inspected
+        let clear = vec![
+            0u8;
+            (self.page_size.x * self.page_size.y) as usize
+                * self.format.bytes_per_pixel()
+        ];
+        let texture =
+            Texture2D::from_data(self.page_size, self.format, &clear, false, self.gl.clone())?;
+        self.pages.push(AtlasPage { texture, packer });
+        Ok((self.pages.len() - 1, position))
+    }
+}
+
+/// Surrounds the image with `padding` pixels copied from its nearest edge
+fn extrude(data: &[u8], size: glm::UVec2, padding: u32, bytes_per_pixel: usize) -> Vec<u8> {
+    let padded_size = size + glm::vec2(padding * 2, padding * 2);
+    if padding == 0 {
+        return data.to_vec();
+    }
+    if size.x == 0 || size.y == 0 {
+        return vec![0; (padded_size.x * padded_size.y) as usize * bytes_per_pixel];
+    }
+
+    let mut res = Vec::with_capacity((padded_size.x * padded_size.y) as usize * bytes_per_pixel);
+    for y in 0..padded_size.y {
+        let source_y = y.saturating_sub(padding).min(size.y - 1);
+        for x in 0..padded_size.x {
+            let source_x = x.saturating_sub(padding).min(size.x - 1);
+            let start = (source_y * size.x + source_x) as usize * bytes_per_pixel;
+            res.extend_from_slice(&data[start..start + bytes_per_pixel]);
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_uses_the_shelf_that_wastes_the_least_height() {
+        let mut packer = ShelfPacker::new(glm::vec2(100, 100));
+        assert_eq!(packer.pack(glm::vec2(60, 30)), Some(glm::vec2(0, 0)));
+        assert_eq!(packer.pack(glm::vec2(60, 10)), Some(glm::vec2(0, 30)));
+
+        // Fits on both shelves, but the second one is closer in height
+        assert_eq!(packer.pack(glm::vec2(10, 8)), Some(glm::vec2(60, 30)));
+        // Only fits on the first one
+        assert_eq!(packer.pack(glm::vec2(10, 20)), Some(glm::vec2(60, 0)));
+        assert_eq!(packer.used_height(), 40);
+    }
+
+    #[test]
+    fn pack_opens_a_new_shelf_when_a_row_is_full() {
+        let mut packer = ShelfPacker::new(glm::vec2(100, 100));
+        assert_eq!(packer.pack(glm::vec2(60, 20)), Some(glm::vec2(0, 0)));
+        assert_eq!(packer.pack(glm::vec2(60, 20)), Some(glm::vec2(0, 20)));
+        assert_eq!(packer.pack(glm::vec2(40, 20)), Some(glm::vec2(60, 0)));
+        assert_eq!(packer.used_height(), 40);
+    }
+
+    #[test]
+    fn pack_returns_none_when_the_page_is_full() {
+        let mut packer = ShelfPacker::new(glm::vec2(100, 100));
+        assert_eq!(packer.pack(glm::vec2(101, 10)), None);
+        assert_eq!(packer.pack(glm::vec2(100, 60)), Some(glm::vec2(0, 0)));
+        assert_eq!(packer.pack(glm::vec2(10, 50)), None);
+        assert_eq!(packer.pack(glm::vec2(100, 40)), Some(glm::vec2(0, 60)));
+        assert_eq!(packer.pack(glm::vec2(1, 1)), None);
+    }
+
+    #[test]
+    fn extrude_copies_the_nearest_edge_pixels() {
+        // 2x2 single channel image
+        let data = [1, 2, 3, 4];
+        let padded = extrude(&data, glm::vec2(2, 2), 1, 1);
+        #[rustfmt::skip]
+        let expected = vec![
+            1, 1, 2, 2,
+            1, 1, 2, 2,
+            3, 3, 4, 4,
+            3, 3, 4, 4,
+        ];
+        assert_eq!(padded, expected);
+    }
+
+    #[test]
+    fn extrude_keeps_whole_pixels_together() {
+        let data = [1, 2, 3, 4];
+        let padded = extrude(&data, glm::vec2(1, 1), 1, 4);
+        assert_eq!(padded.len(), 9 * 4);
+        assert!(padded.chunks_exact(4).all(|pixel| pixel == data));
+
+        assert_eq!(extrude(&data, glm::vec2(1, 1), 0, 4), data.to_vec());
+    }
+}
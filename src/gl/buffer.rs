@@ -0,0 +1,380 @@
+use super::*;
+use std::cell::Cell;
+use std::marker::PhantomData;
+
+/// The binding points a buffer can be bound to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum BufferTarget {
+    Array,
+    Element,
+    Uniform,
+    PixelPack,
+    PixelUnpack,
+    CopyRead,
+    CopyWrite,
+    TransformFeedback,
+}
+
+#[allow(dead_code)]
+impl BufferTarget {
+    pub fn gl_enum(&self) -> types::GLenum {
+        match self {
+            BufferTarget::Array => ARRAY_BUFFER,
+            BufferTarget::Element => ELEMENT_ARRAY_BUFFER,
+            BufferTarget::Uniform => UNIFORM_BUFFER,
+            BufferTarget::PixelPack => PIXEL_PACK_BUFFER,
+            BufferTarget::PixelUnpack => PIXEL_UNPACK_BUFFER,
+            BufferTarget::CopyRead => COPY_READ_BUFFER,
+            BufferTarget::CopyWrite => COPY_WRITE_BUFFER,
+            BufferTarget::TransformFeedback => TRANSFORM_FEEDBACK_BUFFER,
+        }
+    }
+    /// The query for the buffer currently bound to the target
+    pub fn binding_gl_enum(&self) -> types::GLenum {
+        match self {
+            BufferTarget::Array => ARRAY_BUFFER_BINDING,
+            BufferTarget::Element => ELEMENT_ARRAY_BUFFER_BINDING,
+            BufferTarget::Uniform => UNIFORM_BUFFER_BINDING,
+            BufferTarget::PixelPack => PIXEL_PACK_BUFFER_BINDING,
+            BufferTarget::PixelUnpack => PIXEL_UNPACK_BUFFER_BINDING,
+            BufferTarget::CopyRead => COPY_READ_BUFFER_BINDING,
+            BufferTarget::CopyWrite => COPY_WRITE_BUFFER_BINDING,
+            BufferTarget::TransformFeedback => TRANSFORM_FEEDBACK_BUFFER_BINDING,
+        }
+    }
+    /// True for the targets that also have indexed binding points, see `Buffer::bind_base`
+    pub fn is_indexed(&self) -> bool {
+        matches!(
+            self,
+            BufferTarget::Uniform | BufferTarget::TransformFeedback
+        )
+    }
+}
+
+/// Element types of a `Buffer`. Reading a buffer back turns raw bytes into elements, so every bit
+/// pattern has to be a valid value of the type. This rules out bool, char, references and enums
+///
+/// # Safety
+/// The type must not have invalid bit patterns or contain pointers
+pub unsafe trait BufferElement: Copy {}
+
+macro_rules! impl_buffer_element {
+    ($($t:ty),*) => {
+        $(unsafe impl BufferElement for $t {})*
+    };
+}
+
+impl_buffer_element!(u8, i8, u16, i16, u32, i32, u64, i64, f32, f64);
+impl_buffer_element!(
+    glm::Vec2,
+    glm::Vec3,
+    glm::Vec4,
+    glm::Mat2,
+    glm::Mat3,
+    glm::Mat4
+);
+impl_buffer_element!(
+    glm::IVec2,
+    glm::IVec3,
+    glm::IVec4,
+    glm::UVec2,
+    glm::UVec3,
+    glm::UVec4
+);
+impl_buffer_element!(Color);
+unsafe impl<T: BufferElement, const N: usize> BufferElement for [T; N] {}
+
+#[derive(Debug)]
+pub enum BufferError {
+    /// The element range lies outside of the buffer
+    OutOfRange {
+        first: usize,
+        count: usize,
+        len: usize,
+    },
+    /// The driver refused to map the buffer for reading
+    MapFailed,
+    /// The target has no indexed binding points
+    NotIndexed(BufferTarget),
+    /// The indexed binding point is not below the limit of the driver
+    BindingIndexOutOfRange { index: u32, binding_count: u32 },
+}
+
+impl std::error::Error for BufferError {}
+impl std::fmt::Display for BufferError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let to_write = match self {
+            BufferError::OutOfRange { first, count, len } => std::format!(
+                "Elements {}..{} are out of range of a buffer of {} elements",
+                first,
+                first + count,
+                len
+            ),
+            BufferError::MapFailed => String::from("Could not map buffer for reading"),
+            BufferError::NotIndexed(target) => {
+                std::format!("{:?} buffers have no indexed binding points", target)
+            }
+            BufferError::BindingIndexOutOfRange {
+                index,
+                binding_count,
+            } => std::format!(
+                "Binding point {} is out of range, the driver supports {}",
+                index,
+                binding_count
+            ),
+        };
+        write!(f, "{}", to_write)
+    }
+}
+
+/// A GPU buffer holding elements of type `T`, bound to a fixed target
+#[allow(dead_code)]
+pub struct Buffer<T: BufferElement> {
+    gl_handle: u32,
+    target: BufferTarget,
+    /// Number of elements the storage holds
+    len: Cell<usize>,
+    /// Readback is done differently depending on the profile
+    profile: GlProfile,
+    /// Store a Rc to the Gl instance to ensure that we can destroy this resource when dropped
+    gl: std::rc::Rc<Gl>,
+    _element: PhantomData<T>,
+}
+
+#[allow(dead_code)]
+impl<T: BufferElement> Buffer<T> {
+    pub fn new(target: BufferTarget, profile: GlProfile, gl: std::rc::Rc<Gl>) -> Self {
+        let mut gl_handle: types::GLuint = 0;
+        unsafe {
+            gl.GenBuffers(1, &mut gl_handle as *mut _);
+        }
+        assert!(gl_handle > 0);
+        Self {
+            gl_handle,
+            target,
+            len: Cell::new(0),
+            profile,
+            gl,
+            _element: PhantomData,
+        }
+    }
+
+    pub fn gl_handle(&self) -> types::GLuint {
+        self.gl_handle
+    }
+    pub fn target(&self) -> BufferTarget {
+        self.target
+    }
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len.get() == 0
+    }
+    pub fn bind(&self, gl: &super::Gl) {
+        unsafe {
+            gl.BindBuffer(self.target.gl_enum(), self.gl_handle);
+        }
+    }
+    pub fn unbind(target: BufferTarget, gl: &super::Gl) {
+        unsafe {
+            gl.BindBuffer(target.gl_enum(), 0);
+        }
+    }
+    /// Binds the buffer to an indexed binding point of its target. Only valid for uniform and
+    /// transform feedback buffers
+    pub fn bind_base(&self, index: u32, gl: &super::Gl) -> Result<(), BufferError> {
+        let limit = match self.target {
+            BufferTarget::Uniform => MAX_UNIFORM_BUFFER_BINDINGS,
+            BufferTarget::TransformFeedback => MAX_TRANSFORM_FEEDBACK_SEPARATE_ATTRIBS,
+            target => return Err(BufferError::NotIndexed(target)),
+        };
+        let mut binding_count: types::GLint = 0;
+        unsafe {
+            gl.GetIntegerv(limit, &mut binding_count as *mut i32);
+        }
+        if index >= binding_count as u32 {
+            return Err(BufferError::BindingIndexOutOfRange {
+                index,
+                binding_count: binding_count as u32,
+            });
+        }
+
+        unsafe {
+            gl.BindBufferBase(self.target.gl_enum(), index, self.gl_handle);
+        }
+        Ok(())
+    }
+    /// Replaces the storage of the buffer with the given elements
+    pub fn upload(&self, usage: types::GLenum, data: &[T], gl: &super::Gl) {
+        let previous = self.bind_keeping_previous(gl);
+        unsafe {
+            gl.BufferData(
+                self.target.gl_enum(),
+                std::mem::size_of_val(data) as isize,
+                data.as_ptr() as *const std::ffi::c_void,
+                usage,
+            );
+        }
+        self.restore_binding(previous, gl);
+        self.len.set(data.len());
+    }
+    /// Allocates storage for `len` elements with undefined content
+    pub fn allocate(&self, usage: types::GLenum, len: usize, gl: &super::Gl) {
+        let previous = self.bind_keeping_previous(gl);
+        unsafe {
+            gl.BufferData(
+                self.target.gl_enum(),
+                (len * std::mem::size_of::<T>()) as isize,
+                std::ptr::null(),
+                usage,
+            );
+        }
+        self.restore_binding(previous, gl);
+        self.len.set(len);
+    }
+    /// Overwrites elements starting at `first` without reallocating
+    pub fn update(&self, first: usize, data: &[T], gl: &super::Gl) -> Result<(), BufferError> {
+        self.check_range(first, data.len())?;
+
+        let previous = self.bind_keeping_previous(gl);
+        unsafe {
+            gl.BufferSubData(
+                self.target.gl_enum(),
+                (first * std::mem::size_of::<T>()) as isize,
+                std::mem::size_of_val(data) as isize,
+                data.as_ptr() as *const std::ffi::c_void,
+            );
+        }
+        self.restore_binding(previous, gl);
+        Ok(())
+    }
+    /// Copies `count` elements from `source` into this buffer on the GPU, without a round trip
+    /// through the CPU
+    pub fn copy_from(
+        &self,
+        source: &Buffer<T>,
+        source_first: usize,
+        first: usize,
+        count: usize,
+        gl: &super::Gl,
+    ) -> Result<(), BufferError> {
+        source.check_range(source_first, count)?;
+        self.check_range(first, count)?;
+
+        let element_size = std::mem::size_of::<T>();
+        let previous_read = current_buffer_binding(BufferTarget::CopyRead, gl);
+        let previous_write = current_buffer_binding(BufferTarget::CopyWrite, gl);
+        unsafe {
+            gl.BindBuffer(COPY_READ_BUFFER, source.gl_handle);
+            gl.BindBuffer(COPY_WRITE_BUFFER, self.gl_handle);
+            gl.CopyBufferSubData(
+                COPY_READ_BUFFER,
+                COPY_WRITE_BUFFER,
+                (source_first * element_size) as isize,
+                (first * element_size) as isize,
+                (count * element_size) as isize,
+            );
+            gl.BindBuffer(COPY_READ_BUFFER, previous_read);
+            gl.BindBuffer(COPY_WRITE_BUFFER, previous_write);
+        }
+        Ok(())
+    }
+    /// Reads `count` elements starting at `first` back to the CPU. Stalls until the GPU has
+    /// finished writing them
+    pub fn read(&self, first: usize, count: usize, gl: &super::Gl) -> Result<Vec<T>, BufferError> {
+        self.check_range(first, count)?;
+
+        let element_size = std::mem::size_of::<T>();
+        let offset = (first * element_size) as isize;
+        let size = count * element_size;
+        let mut data: Vec<T> = Vec::with_capacity(count);
+
+        // Bound to COPY_READ_BUFFER so that reading does not disturb the bindings of the target
+        let previous = current_buffer_binding(BufferTarget::CopyRead, gl);
+        unsafe {
+            gl.BindBuffer(COPY_READ_BUFFER, self.gl_handle);
+        }
+
+        let result = match self.profile {
+            GlProfile::Core43 => {
+                unsafe {
+                    gl.GetBufferSubData(
+                        COPY_READ_BUFFER,
+                        offset,
+                        size as isize,
+                        data.as_mut_ptr() as *mut std::ffi::c_void,
+                    );
+                    data.set_len(count);
+                }
+                Ok(data)
+            }
+            // ES has no GetBufferSubData, the buffer has to be mapped instead
+            GlProfile::ES3 => unsafe {
+                let mapped =
+                    gl.MapBufferRange(COPY_READ_BUFFER, offset, size as isize, MAP_READ_BIT);
+                if mapped.is_null() {
+                    Err(BufferError::MapFailed)
+                } else {
+                    std::ptr::copy_nonoverlapping(
+                        mapped as *const u8,
+                        data.as_mut_ptr() as *mut u8,
+                        size,
+                    );
+                    data.set_len(count);
+                    gl.UnmapBuffer(COPY_READ_BUFFER);
+                    Ok(data)
+                }
+            },
+        };
+
+        unsafe {
+            gl.BindBuffer(COPY_READ_BUFFER, previous);
+        }
+        result
+    }
+    /// Reads back the whole buffer
+    pub fn read_all(&self, gl: &super::Gl) -> Result<Vec<T>, BufferError> {
+        self.read(0, self.len(), gl)
+    }
+    /// Binds the buffer and returns what was bound to its target before. Unbinding instead would
+    /// detach element buffers from the bound Vao
+    fn bind_keeping_previous(&self, gl: &super::Gl) -> types::GLuint {
+        let previous = current_buffer_binding(self.target, gl);
+        self.bind(gl);
+        previous
+    }
+    fn restore_binding(&self, previous: types::GLuint, gl: &super::Gl) {
+        unsafe {
+            gl.BindBuffer(self.target.gl_enum(), previous);
+        }
+    }
+    fn check_range(&self, first: usize, count: usize) -> Result<(), BufferError> {
+        if first + count > self.len.get() {
+            return Err(BufferError::OutOfRange {
+                first,
+                count,
+                len: self.len.get(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<T: BufferElement> Drop for Buffer<T> {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.DeleteBuffers(1, &self.gl_handle as *const _);
+        }
+    }
+}
+
+/// The buffer currently bound to `target`
+fn current_buffer_binding(target: BufferTarget, gl: &Gl) -> types::GLuint {
+    let mut binding: types::GLint = 0;
+    unsafe {
+        gl.GetIntegerv(target.binding_gl_enum(), &mut binding as *mut i32);
+    }
+    binding as types::GLuint
+}
@@ -151,3 +151,19 @@ impl Camera {
         self.projection.matrix() * self.view_matrix()
     }
 }
+
+/// Per-frame camera data shared by all shaders through the camera uniform block
+#[derive(Debug, Clone, Copy)]
+pub struct CameraUniforms {
+    pub view_projection: glm::Mat4,
+}
+
+crate::impl_std140!(CameraUniforms { view_projection });
+
+impl From<&Camera> for CameraUniforms {
+    fn from(camera: &Camera) -> Self {
+        Self {
+            view_projection: camera.view_projection_matrix(),
+        }
+    }
+}
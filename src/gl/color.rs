@@ -1,4 +1,5 @@
 #[derive(Debug, Copy, Clone)]
+#[repr(C)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -0,0 +1,373 @@
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+    Info,
+}
+
+/// A single message from a driver's compile or link log, parsed into its parts
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct ShaderDiagnostic {
+    /// None for link messages that do not belong to a single stage
+    pub stage: Option<ShaderStage>,
+    /// The source string number reported by the driver. Refers to an included file if the source
+    /// went through the preprocessor
+    pub source_index: u32,
+    /// Name of the file the message is about, when known
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub severity: DiagnosticSeverity,
+    pub message: String,
+    /// The source line the message points at
+    pub excerpt: Option<String>,
+}
+
+impl ShaderCompilationError {
+    /// Parses the log of a failed compile or link. `source` is the source handed to the driver,
+    /// used to pick out the lines the diagnostics refer to
+    pub fn new(log: String, stage: Option<ShaderStage>, source: Option<&str>) -> Self {
+        let mut diagnostics = parse_shader_log(&log, stage);
+
+        if let Some(source) = source {
+            let line_map = build_line_map(source);
+            let source_lines: Vec<&str> = source.lines().collect();
+
+            for diagnostic in &mut diagnostics {
+                if let Some(line) = diagnostic.line {
+                    diagnostic.excerpt = line_map
+                        .iter()
+                        .position(|location| *location == Some((diagnostic.source_index, line)))
+                        .and_then(|i| source_lines.get(i))
+                        .map(|excerpt| String::from(*excerpt));
+                }
+            }
+        }
+
+        Self { log, diagnostics }
+    }
+    /// Gives the diagnostics the names of the files they point into. Index i of `source_names` is
+    /// the name of source string number i, as produced by the preprocessor
+    pub fn set_source_names(&mut self, source_names: &[String]) {
+        for diagnostic in &mut self.diagnostics {
+            diagnostic.file = source_names.get(diagnostic.source_index as usize).cloned();
+        }
+    }
+}
+
+impl std::fmt::Display for ShaderCompilationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Fall back to the raw log if it was in a format that could not be parsed
+        if self.diagnostics.is_empty() {
+            return write!(f, "{}", self.log);
+        }
+
+        for diagnostic in &self.diagnostics {
+            write!(f, "\n{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for ShaderDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            DiagnosticSeverity::Error => "error",
+            DiagnosticSeverity::Warning => "warning",
+            DiagnosticSeverity::Info => "info",
+        };
+
+        let file = match &self.file {
+            Some(file) => file.clone(),
+            None => std::format!("<source {}>", self.source_index),
+        };
+
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(f, "{}:{}:{}: ", file, line, column)?,
+            (Some(line), None) => write!(f, "{}:{}: ", file, line)?,
+            _ => {}
+        }
+        write!(f, "{}: {}", severity, self.message)?;
+
+        if let (Some(line), Some(excerpt)) = (self.line, &self.excerpt) {
+            let gutter = line.to_string().len();
+            write!(f, "\n {} | {}", line, excerpt)?;
+
+            if let Some(column) = self.column {
+                let offset = (column as usize).saturating_sub(1);
+                write!(f, "\n {} | {}^", " ".repeat(gutter), " ".repeat(offset))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses a driver log into diagnostics. Understands the formats of Mesa
+/// (`0:12(5): error: message`), NVIDIA (`0(12) : error C0000: message`) and ANGLE/WebGL as well
+/// as most Windows drivers (`ERROR: 0:12: message`). Lines in other formats are kept as
+/// diagnostics without a location
+pub fn parse_shader_log(log: &str, stage: Option<ShaderStage>) -> Vec<ShaderDiagnostic> {
+    log.lines()
+        .map(|line| line.trim_matches(|c: char| c.is_whitespace() || c == '\0'))
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            parse_angle_line(line, stage)
+                .or_else(|| parse_mesa_line(line, stage))
+                .or_else(|| parse_nvidia_line(line, stage))
+                .unwrap_or_else(|| ShaderDiagnostic {
+                    stage,
+                    source_index: 0,
+                    file: None,
+                    line: None,
+                    column: None,
+                    severity: if line.to_lowercase().contains("error") {
+                        DiagnosticSeverity::Error
+                    } else {
+                        DiagnosticSeverity::Info
+                    },
+                    message: String::from(line),
+                    excerpt: None,
+                })
+        })
+        .collect()
+}
+
+/// `ERROR: 0:12: 'foo' : undeclared identifier`
+fn parse_angle_line(line: &str, stage: Option<ShaderStage>) -> Option<ShaderDiagnostic> {
+    let (severity, rest) = line.split_once(':')?;
+    let severity = parse_severity(severity)?;
+    let rest = rest.trim_start();
+
+    let location = take_number(rest).and_then(|(source_index, rest)| {
+        let rest = rest.strip_prefix(':')?;
+        let (line, rest) = take_number(rest)?;
+        let rest = rest.strip_prefix(':')?;
+        Some((source_index, line, rest))
+    });
+
+    let (source_index, line, message) = match location {
+        Some((source_index, line, message)) => (source_index, Some(line), message),
+        None => (0, None, rest),
+    };
+
+    Some(ShaderDiagnostic {
+        stage,
+        source_index,
+        file: None,
+        line,
+        column: None,
+        severity,
+        message: String::from(message.trim()),
+        excerpt: None,
+    })
+}
+
+/// `0:12(5): error: 'foo' undeclared`
+fn parse_mesa_line(line: &str, stage: Option<ShaderStage>) -> Option<ShaderDiagnostic> {
+    let (source_index, rest) = take_number(line)?;
+    let rest = rest.strip_prefix(':')?;
+    let (line, rest) = take_number(rest)?;
+    let rest = rest.strip_prefix('(')?;
+    let (column, rest) = take_number(rest)?;
+    let rest = rest.strip_prefix("):")?;
+    let (severity, message) = rest.split_once(':')?;
+
+    Some(ShaderDiagnostic {
+        stage,
+        source_index,
+        file: None,
+        line: Some(line),
+        column: Some(column),
+        severity: parse_severity(severity)?,
+        message: String::from(message.trim()),
+        excerpt: None,
+    })
+}
+
+/// `0(12) : error C1008: undefined variable "foo"`
+fn parse_nvidia_line(line: &str, stage: Option<ShaderStage>) -> Option<ShaderDiagnostic> {
+    let (source_index, rest) = take_number(line)?;
+    let rest = rest.strip_prefix('(')?;
+    let (line, rest) = take_number(rest)?;
+    let rest = rest.strip_prefix(')')?.trim_start().strip_prefix(':')?;
+    let (severity_and_code, message) = rest.split_once(':')?;
+    let severity = severity_and_code.split_whitespace().next()?;
+
+    Some(ShaderDiagnostic {
+        stage,
+        source_index,
+        file: None,
+        line: Some(line),
+        column: None,
+        severity: parse_severity(severity)?,
+        message: String::from(message.trim()),
+        excerpt: None,
+    })
+}
+
+fn parse_severity(severity: &str) -> Option<DiagnosticSeverity> {
+    match &severity.trim().to_lowercase()[..] {
+        "error" | "fatal error" => Some(DiagnosticSeverity::Error),
+        "warning" => Some(DiagnosticSeverity::Warning),
+        "info" | "note" => Some(DiagnosticSeverity::Info),
+        _ => None,
+    }
+}
+
+fn take_number(s: &str) -> Option<(u32, &str)> {
+    let end = s
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or_else(|| s.len());
+    let number = s[..end].parse().ok()?;
+    Some((number, &s[end..]))
+}
+
+/// Works out the (source string number, line number) the driver assigns to every line of a
+/// source, taking `#line` directives into account. The directives themselves map to None since
+/// they share their number with the line they renumber
+fn build_line_map(source: &str) -> Vec<Option<(u32, u32)>> {
+    let mut current = (0, 1);
+
+    source
+        .lines()
+        .map(|line| {
+            let location = current;
+            current.1 += 1;
+
+            let directive = line.trim_start().strip_prefix('#').map(|d| d.trim_start());
+            if let Some(arguments) = directive.and_then(|d| d.strip_prefix("line")) {
+                let mut arguments = arguments.split_whitespace().map(|a| a.parse::<u32>());
+
+                if let Some(Ok(line)) = arguments.next() {
+                    current.1 = line;
+                    if let Some(Ok(source_index)) = arguments.next() {
+                        current.0 = source_index;
+                    }
+                    return None;
+                }
+            }
+
+            Some(location)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_single(line: &str) -> ShaderDiagnostic {
+        let mut diagnostics = parse_shader_log(line, Some(ShaderStage::Fragment));
+        assert_eq!(diagnostics.len(), 1);
+        diagnostics.remove(0)
+    }
+
+    #[test]
+    fn parses_mesa_lines() {
+        let diagnostic = parse_single("0:12(5): error: `foo' undeclared");
+
+        assert_eq!(diagnostic.stage, Some(ShaderStage::Fragment));
+        assert_eq!(diagnostic.source_index, 0);
+        assert_eq!(diagnostic.line, Some(12));
+        assert_eq!(diagnostic.column, Some(5));
+        assert_eq!(diagnostic.severity, DiagnosticSeverity::Error);
+        assert_eq!(diagnostic.message, "`foo' undeclared");
+    }
+
+    #[test]
+    fn parses_nvidia_lines() {
+        let diagnostic = parse_single("2(12) : warning C7050: \"foo\" might be used before set");
+
+        assert_eq!(diagnostic.source_index, 2);
+        assert_eq!(diagnostic.line, Some(12));
+        assert_eq!(diagnostic.column, None);
+        assert_eq!(diagnostic.severity, DiagnosticSeverity::Warning);
+        assert_eq!(diagnostic.message, "\"foo\" might be used before set");
+    }
+
+    #[test]
+    fn parses_angle_lines() {
+        let diagnostic = parse_single("ERROR: 0:12: 'foo' : undeclared identifier");
+
+        assert_eq!(diagnostic.source_index, 0);
+        assert_eq!(diagnostic.line, Some(12));
+        assert_eq!(diagnostic.column, None);
+        assert_eq!(diagnostic.severity, DiagnosticSeverity::Error);
+        assert_eq!(diagnostic.message, "'foo' : undeclared identifier");
+
+        let summary = parse_single("ERROR: 1 compilation errors.  No code generated.");
+        assert_eq!(summary.line, None);
+        assert_eq!(summary.severity, DiagnosticSeverity::Error);
+        assert_eq!(summary.message, "1 compilation errors.  No code generated.");
+    }
+
+    #[test]
+    fn keeps_unparseable_lines_without_location() {
+        let diagnostics = parse_shader_log(
+            "Vertex info\n-----------\nlink error: too many varyings\0",
+            None,
+        );
+
+        assert_eq!(diagnostics.len(), 3);
+        assert!(diagnostics
+            .iter()
+            .all(|d| d.line.is_none() && d.stage.is_none()));
+        assert_eq!(diagnostics[0].severity, DiagnosticSeverity::Info);
+        assert_eq!(diagnostics[0].message, "Vertex info");
+        assert_eq!(diagnostics[2].severity, DiagnosticSeverity::Error);
+        assert_eq!(diagnostics[2].message, "link error: too many varyings");
+    }
+
+    #[test]
+    fn line_map_follows_line_directives() {
+        let source = "#version 300 es\n#line 1 1\nfloat a;\nfloat b;\n#line 2 0\nvoid main() {}\n";
+
+        assert_eq!(
+            build_line_map(source),
+            vec![
+                Some((0, 1)),
+                None,
+                Some((1, 1)),
+                Some((1, 2)),
+                None,
+                Some((0, 2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn maps_diagnostics_into_included_files() {
+        let source = "#version 300 es\n\
+                      #line 1 1\n\
+                      float helper() {\n\
+                      \x20   return undefined;\n\
+                      }\n\
+                      #line 2 0\n\
+                      void main() { helper(); }\n";
+        let log = "1:2(12): error: `undefined' undeclared\n0:2(1): warning: unused result\n";
+
+        let mut error = ShaderCompilationError::new(
+            String::from(log),
+            Some(ShaderStage::Fragment),
+            Some(source),
+        );
+        error.set_source_names(&[String::from("main.frag"), String::from("common.glsl")]);
+
+        let included = &error.diagnostics[0];
+        assert_eq!(included.file.as_deref(), Some("common.glsl"));
+        assert_eq!(included.excerpt.as_deref(), Some("    return undefined;"));
+        assert_eq!(
+            included.to_string(),
+            "common.glsl:2:12: error: `undefined' undeclared\n \
+             2 |     return undefined;\n   \
+             |            ^"
+        );
+
+        let main = &error.diagnostics[1];
+        assert_eq!(main.file.as_deref(), Some("main.frag"));
+        assert_eq!(main.excerpt.as_deref(), Some("void main() { helper(); }"));
+    }
+}
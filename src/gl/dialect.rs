@@ -0,0 +1,248 @@
+use super::*;
+
+/// Rewrites a GLSL source written for either profile so that it compiles on the given one. The
+/// version header, default precision statements, layout qualifiers and extension directives are
+/// adjusted in a way that keeps line numbers in driver logs matching the source as written
+pub fn translate_glsl(source: &str, stage: ShaderStage, profile: GlProfile) -> String {
+    let mut lines: Vec<String> = source.lines().map(String::from).collect();
+
+    let version = match profile {
+        GlProfile::Core43 => "#version 430 core",
+        GlProfile::ES3 => "#version 300 es",
+    };
+
+    // Only comments may come before the version, those are left in place
+    let first_body_line = match find_version_directive(source) {
+        Some(version_line) => {
+            lines[version_line] = String::from(version);
+            version_line + 1
+        }
+        None => {
+            // The version has to come before any code, so the numbering is reset after it
+            lines.insert(0, String::from("#line 1"));
+            lines.insert(0, String::from(version));
+            2
+        }
+    };
+
+    let mut has_float_precision = false;
+
+    for line in lines.iter_mut().skip(first_body_line) {
+        if directive_name(line) == Some("extension") {
+            if !is_extension_supported(line, profile) {
+                line.clear();
+            }
+        } else if is_precision_statement(line) {
+            match profile {
+                // Precision statements are allowed but meaningless on desktop GL
+                GlProfile::Core43 => line.clear(),
+                GlProfile::ES3 => has_float_precision |= line.contains("float"),
+            }
+        } else if profile == GlProfile::ES3 && line.contains("layout") {
+            *line = rewrite_layout_qualifiers_for_es3(line, stage);
+        }
+    }
+
+    // ES fragment shaders have no default float precision, so one has to be provided. It is put
+    // in front of the first line of actual code to not shift any line numbers
+    if profile == GlProfile::ES3 && stage == ShaderStage::Fragment && !has_float_precision {
+        let first_code_line = lines.iter().skip(first_body_line).position(|line| {
+            let trimmed = line.trim();
+            !trimmed.is_empty()
+                && !trimmed.starts_with("//")
+                && !matches!(
+                    directive_name(line),
+                    Some("extension") | Some("define") | Some("line") | Some("pragma")
+                )
+        });
+
+        match first_code_line.map(|i| i + first_body_line) {
+            Some(i) if !lines[i].trim_start().starts_with('#') => {
+                lines[i] = std::format!("precision mediump float; {}", lines[i]);
+            }
+            // Code starts with a conditional directive, so the statement needs its own line and
+            // the numbering is restored after it
+            Some(i) => {
+                let line_number = line_number_at(&lines, i);
+                lines.insert(i, String::from("precision mediump float;"));
+                lines.insert(i + 1, std::format!("#line {}", line_number));
+            }
+            // There is no code at all, so no numbering to keep
+            None => lines.push(String::from("precision mediump float;")),
+        }
+    }
+
+    let mut res = lines.join("\n");
+    res.push('\n');
+    res
+}
+
+/// The number the compiler gives to the line at `index`, taking `#line` directives into account
+fn line_number_at(lines: &[String], index: usize) -> usize {
+    let mut line_number = 1;
+    for line in &lines[..index] {
+        line_number = match directive_name(line) {
+            Some("line") => line
+                .trim_start()
+                .trim_start_matches('#')
+                .trim_start()
+                .trim_start_matches("line")
+                .split_whitespace()
+                .next()
+                .and_then(|number| number.parse().ok())
+                .unwrap_or(line_number + 1),
+            _ => line_number + 1,
+        };
+    }
+    line_number
+}
+
+/// Returns the name of a preprocessor directive, i.e. "version" for "#version 300 es"
+fn directive_name(line: &str) -> Option<&str> {
+    let directive = line.trim_start().strip_prefix('#')?.trim_start();
+    let end = directive
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .unwrap_or_else(|| directive.len());
+    Some(&directive[..end])
+}
+
+fn is_precision_statement(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.starts_with("precision ") && trimmed.ends_with(';')
+}
+
+/// OES extensions only exist on ES and ARB extensions only on desktop GL
+fn is_extension_supported(line: &str, profile: GlProfile) -> bool {
+    let name = line
+        .trim_start()
+        .trim_start_matches('#')
+        .trim_start()
+        .trim_start_matches("extension")
+        .split(':')
+        .next()
+        .unwrap_or("")
+        .trim();
+
+    match profile {
+        GlProfile::Core43 => !name.starts_with("GL_OES_"),
+        GlProfile::ES3 => !name.starts_with("GL_ARB_"),
+    }
+}
+
+/// GLSL ES 3.00 only allows `location` on vertex inputs and fragment outputs, and has no
+/// `binding` at all. Those are removed, along with the whole `layout(...)` if nothing remains
+fn rewrite_layout_qualifiers_for_es3(line: &str, stage: ShaderStage) -> String {
+    let layout_start = match line.find("layout") {
+        Some(start) => start,
+        None => return String::from(line),
+    };
+    let after_keyword = &line[layout_start + "layout".len()..];
+    if !after_keyword.trim_start().starts_with('(') {
+        return String::from(line);
+    }
+    let open = line.len() - after_keyword.trim_start().len();
+    let close = match line[open..].find(')') {
+        Some(close) => open + close,
+        None => return String::from(line),
+    };
+
+    let declaration = &line[close + 1..];
+    // Interpolation, auxiliary and precision qualifiers may come before the storage qualifier
+    let storage = declaration
+        .split_whitespace()
+        .find(|word| {
+            !matches!(
+                *word,
+                "flat"
+                    | "smooth"
+                    | "noperspective"
+                    | "centroid"
+                    | "sample"
+                    | "patch"
+                    | "invariant"
+                    | "precise"
+                    | "highp"
+                    | "mediump"
+                    | "lowp"
+            )
+        })
+        .unwrap_or("");
+    let location_allowed = match stage {
+        ShaderStage::Vertex => storage != "out",
+        ShaderStage::Fragment => storage != "in",
+        // Other stages do not exist on ES3 in the first place
+        _ => true,
+    };
+
+    let kept_qualifiers: Vec<&str> = line[open + 1..close]
+        .split(',')
+        .map(|q| q.trim())
+        .filter(|q| {
+            let name = q.split('=').next().unwrap_or("").trim();
+            name != "binding" && (name != "location" || location_allowed)
+        })
+        .collect();
+
+    if kept_qualifiers.is_empty() {
+        std::format!("{}{}", &line[..layout_start], declaration.trim_start())
+    } else {
+        std::format!(
+            "{}layout({}){}",
+            &line[..layout_start],
+            kept_qualifiers.join(", "),
+            declaration
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_after_leading_comment_is_replaced() {
+        let source = "// License header\n#version 430 core\nvoid main() {}\n";
+        let result = translate_glsl(source, ShaderStage::Vertex, GlProfile::ES3);
+
+        assert_eq!(
+            result,
+            "// License header\n#version 300 es\nvoid main() {}\n"
+        );
+    }
+
+    #[test]
+    fn inserted_precision_restores_line_numbers() {
+        let source = "#version 300 es\n#ifdef X\nout vec4 color;\n#endif\n";
+        let result = translate_glsl(source, ShaderStage::Fragment, GlProfile::ES3);
+        let lines: Vec<&str> = result.lines().collect();
+
+        assert_eq!(lines[1], "precision mediump float;");
+        assert_eq!(lines[2], "#line 2");
+        assert_eq!(lines[3], "#ifdef X");
+    }
+
+    #[test]
+    fn location_is_removed_after_interpolation_qualifiers() {
+        assert_eq!(
+            rewrite_layout_qualifiers_for_es3(
+                "layout(location = 0) flat out vec3 n;",
+                ShaderStage::Vertex
+            ),
+            "flat out vec3 n;"
+        );
+        assert_eq!(
+            rewrite_layout_qualifiers_for_es3(
+                "layout(location = 1) centroid highp in vec2 uv;",
+                ShaderStage::Fragment
+            ),
+            "centroid highp in vec2 uv;"
+        );
+        assert_eq!(
+            rewrite_layout_qualifiers_for_es3(
+                "layout(location = 2) in highp vec3 position;",
+                ShaderStage::Vertex
+            ),
+            "layout(location = 2) in highp vec3 position;"
+        );
+    }
+}
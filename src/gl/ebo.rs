@@ -0,0 +1,101 @@
+use super::*;
+use std::cell::Cell;
+
+/// An integer type that can be used for vertex indices
+pub trait IndexType: Copy {
+    const DATA_TYPE: types::GLenum;
+}
+
+impl IndexType for u8 {
+    const DATA_TYPE: types::GLenum = UNSIGNED_BYTE;
+}
+impl IndexType for u16 {
+    const DATA_TYPE: types::GLenum = UNSIGNED_SHORT;
+}
+impl IndexType for u32 {
+    const DATA_TYPE: types::GLenum = UNSIGNED_INT;
+}
+
+/// Size in bytes of a single index of the given type
+pub fn index_type_size(index_type: types::GLenum) -> usize {
+    match index_type {
+        UNSIGNED_BYTE => 1,
+        UNSIGNED_SHORT => 2,
+        _ => 4,
+    }
+}
+
+/// A buffer of vertex indices. The element buffer binding is part of the Vao state, so it is
+/// normally owned by a Vao, see `Vao::set_element_buffer`
+pub struct ElementBuffer {
+    gl_handle: u32,
+    /// Type and count of the indices last uploaded
+    index_type: Cell<types::GLenum>,
+    index_count: Cell<usize>,
+    /// Store a Rc to the Gl instance to ensure that we can destroy this resource when dropped
+    gl: std::rc::Rc<Gl>,
+}
+
+impl ElementBuffer {
+    pub fn new(gl: std::rc::Rc<Gl>) -> Self {
+        let mut gl_handle: types::GLuint = 0;
+        unsafe {
+            gl.GenBuffers(1, &mut gl_handle as *mut _);
+        }
+        assert!(gl_handle > 0);
+        Self {
+            gl_handle,
+            index_type: Cell::new(UNSIGNED_SHORT),
+            index_count: Cell::new(0),
+            gl,
+        }
+    }
+
+    /// Also attaches the buffer to the currently bound Vao
+    pub fn bind(&self, gl: &super::Gl) {
+        unsafe {
+            gl.BindBuffer(super::ELEMENT_ARRAY_BUFFER, self.gl_handle);
+        }
+    }
+    pub fn unbind(gl: &super::Gl) {
+        unsafe {
+            gl.BindBuffer(super::ELEMENT_ARRAY_BUFFER, 0);
+        }
+    }
+    pub fn index_type(&self) -> types::GLenum {
+        self.index_type.get()
+    }
+    #[allow(dead_code)]
+    pub fn index_count(&self) -> usize {
+        self.index_count.get()
+    }
+    /// Replaces the content of the buffer. Since binding the buffer attaches it to the bound Vao,
+    /// this should only be called with the owning Vao bound. `Vao::upload_indices` takes care of
+    /// that
+    pub fn upload_indices<T: IndexType>(
+        &self,
+        usage: crate::gl::types::GLenum,
+        indices: &[T],
+        gl: &crate::gl::Gl,
+    ) {
+        self.bind(gl);
+        unsafe {
+            gl.BufferData(
+                crate::gl::ELEMENT_ARRAY_BUFFER,
+                std::mem::size_of_val(indices) as isize,
+                indices.as_ptr() as *const std::ffi::c_void,
+                usage,
+            );
+        }
+        self.index_type.set(T::DATA_TYPE);
+        self.index_count.set(indices.len());
+    }
+}
+
+impl Drop for ElementBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.DeleteBuffers(1, &self.gl_handle as *const _);
+        }
+    }
+}
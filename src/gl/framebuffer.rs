@@ -0,0 +1,468 @@
+use super::*;
+
+/// Image storage that can only be rendered to, not sampled. Cheaper than a texture for
+/// attachments that are never read back, and the only way to multisample on ES3
+#[allow(dead_code)]
+pub struct Renderbuffer {
+    gl_handle: u32,
+    size: glm::UVec2,
+    format: TextureFormat,
+    /// 0 for a single sampled buffer
+    samples: i32,
+    /// Store a Rc to the Gl instance to ensure that we can destroy this resource when dropped
+    gl: std::rc::Rc<Gl>,
+}
+
+#[allow(dead_code)]
+impl Renderbuffer {
+    pub fn new(size: glm::UVec2, format: TextureFormat, samples: i32, gl: std::rc::Rc<Gl>) -> Self {
+        let mut gl_handle: types::GLuint = 0;
+        unsafe {
+            gl.GenRenderbuffers(1, &mut gl_handle as *mut _);
+        }
+        assert!(gl_handle > 0);
+
+        unsafe {
+            gl.BindRenderbuffer(RENDERBUFFER, gl_handle);
+            gl.RenderbufferStorageMultisample(
+                RENDERBUFFER,
+                samples,
+                format.internal_format(),
+                size.x as i32,
+                size.y as i32,
+            );
+            gl.BindRenderbuffer(RENDERBUFFER, 0);
+        }
+
+        Self {
+            gl_handle,
+            size,
+            format,
+            samples,
+            gl,
+        }
+    }
+    pub fn gl_handle(&self) -> types::GLuint {
+        self.gl_handle
+    }
+    pub fn size(&self) -> glm::UVec2 {
+        self.size
+    }
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+    pub fn samples(&self) -> i32 {
+        self.samples
+    }
+}
+
+impl Drop for Renderbuffer {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.DeleteRenderbuffers(1, &self.gl_handle as *const _);
+        }
+    }
+}
+
+/// Image a framebuffer renders into. Owned by the framebuffer
+#[allow(dead_code)]
+pub enum FramebufferAttachment {
+    Texture(Texture2D),
+    Renderbuffer(Renderbuffer),
+}
+
+#[allow(dead_code)]
+impl FramebufferAttachment {
+    pub fn size(&self) -> glm::UVec2 {
+        match self {
+            FramebufferAttachment::Texture(texture) => texture.size(),
+            FramebufferAttachment::Renderbuffer(renderbuffer) => renderbuffer.size(),
+        }
+    }
+    pub fn format(&self) -> TextureFormat {
+        match self {
+            FramebufferAttachment::Texture(texture) => texture.format(),
+            FramebufferAttachment::Renderbuffer(renderbuffer) => renderbuffer.format(),
+        }
+    }
+    fn attach(&self, attachment_point: types::GLenum, gl: &Gl) {
+        unsafe {
+            match self {
+                FramebufferAttachment::Texture(texture) => gl.FramebufferTexture2D(
+                    FRAMEBUFFER,
+                    attachment_point,
+                    TEXTURE_2D,
+                    texture.gl_handle(),
+                    0,
+                ),
+                FramebufferAttachment::Renderbuffer(renderbuffer) => gl.FramebufferRenderbuffer(
+                    FRAMEBUFFER,
+                    attachment_point,
+                    RENDERBUFFER,
+                    renderbuffer.gl_handle(),
+                ),
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum FramebufferError {
+    /// CheckFramebufferStatus reported something other than FRAMEBUFFER_COMPLETE
+    Incomplete(types::GLenum),
+    /// A depth format was used as a color attachment or the other way around
+    WrongAttachmentFormat(TextureFormat),
+    /// All attachments need to be the same size
+    SizeMismatch {
+        size: glm::UVec2,
+        attachment_size: glm::UVec2,
+    },
+    /// Blitting between framebuffers of different size while resolving multisampling
+    ResolveSizeMismatch,
+    Texture(TextureError),
+}
+
+impl std::error::Error for FramebufferError {}
+impl std::fmt::Display for FramebufferError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let to_write = match self {
+            FramebufferError::Incomplete(status) => std::format!(
+                "Framebuffer is incomplete: {}",
+                framebuffer_status_description(*status)
+            ),
+            FramebufferError::WrongAttachmentFormat(format) => std::format!(
+                "Format {:?} cannot be used for this framebuffer attachment",
+                format
+            ),
+            FramebufferError::SizeMismatch {
+                size,
+                attachment_size,
+            } => std::format!(
+                "Attachment of size {}x{} does not match framebuffer of size {}x{}",
+                attachment_size.x,
+                attachment_size.y,
+                size.x,
+                size.y
+            ),
+            FramebufferError::ResolveSizeMismatch => String::from(
+                "Multisampled framebuffers can only be resolved into framebuffers of equal size",
+            ),
+            FramebufferError::Texture(e) => {
+                std::format!("Could not create framebuffer texture: {}", e)
+            }
+        };
+        write!(f, "{}", to_write)
+    }
+}
+
+impl From<TextureError> for FramebufferError {
+    fn from(e: TextureError) -> Self {
+        FramebufferError::Texture(e)
+    }
+}
+
+/// Explains the status returned by CheckFramebufferStatus
+pub fn framebuffer_status_description(status: types::GLenum) -> &'static str {
+    match status {
+        FRAMEBUFFER_COMPLETE => "complete",
+        FRAMEBUFFER_UNDEFINED => "the default framebuffer does not exist",
+        FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "an attachment is not renderable or has no storage",
+        FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => "there are no attachments",
+        FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => "a draw buffer refers to a missing attachment",
+        FRAMEBUFFER_INCOMPLETE_READ_BUFFER => "the read buffer refers to a missing attachment",
+        FRAMEBUFFER_UNSUPPORTED => "the combination of attachment formats is not supported",
+        FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => "the attachments have different sample counts",
+        FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => "the attachments have different layer counts",
+        _ => "unknown status",
+    }
+}
+
+/// The framebuffer and viewport that were current before binding a `Framebuffer`, to be put back
+/// with `restore`
+#[derive(Debug, Clone, Copy)]
+pub struct FramebufferBinding {
+    draw_framebuffer: types::GLuint,
+    read_framebuffer: types::GLuint,
+    viewport: [i32; 4],
+}
+
+impl FramebufferBinding {
+    /// Captures the current bindings
+    pub fn current(gl: &Gl) -> Self {
+        let mut draw_framebuffer: types::GLint = 0;
+        let mut read_framebuffer: types::GLint = 0;
+        let mut viewport = [0; 4];
+        unsafe {
+            gl.GetIntegerv(DRAW_FRAMEBUFFER_BINDING, &mut draw_framebuffer as *mut i32);
+            gl.GetIntegerv(READ_FRAMEBUFFER_BINDING, &mut read_framebuffer as *mut i32);
+            gl.GetIntegerv(VIEWPORT, viewport.as_mut_ptr());
+        }
+        Self {
+            draw_framebuffer: draw_framebuffer as u32,
+            read_framebuffer: read_framebuffer as u32,
+            viewport,
+        }
+    }
+    pub fn restore(&self, gl: &Gl) {
+        unsafe {
+            gl.BindFramebuffer(DRAW_FRAMEBUFFER, self.draw_framebuffer);
+            gl.BindFramebuffer(READ_FRAMEBUFFER, self.read_framebuffer);
+            gl.Viewport(
+                self.viewport[0],
+                self.viewport[1],
+                self.viewport[2],
+                self.viewport[3],
+            );
+        }
+    }
+}
+
+/// A render target other than the window
+#[allow(dead_code)]
+pub struct Framebuffer {
+    gl_handle: u32,
+    size: glm::UVec2,
+    color_attachments: Vec<FramebufferAttachment>,
+    depth_attachment: Option<FramebufferAttachment>,
+    /// Store a Rc to the Gl instance to ensure that we can destroy this resource when dropped
+    gl: std::rc::Rc<Gl>,
+}
+
+#[allow(dead_code)]
+impl Framebuffer {
+    /// Creates a framebuffer from attachments of equal size. Color attachment i is written by
+    /// fragment shader output location i. The depth attachment also gets used as the stencil
+    /// attachment if its format has stencil bits
+    pub fn new(
+        color_attachments: Vec<FramebufferAttachment>,
+        depth_attachment: Option<FramebufferAttachment>,
+        gl: std::rc::Rc<Gl>,
+    ) -> Result<Self, FramebufferError> {
+        let size = color_attachments
+            .first()
+            .or(depth_attachment.as_ref())
+            .map(|attachment| attachment.size())
+            .unwrap_or_else(|| glm::vec2(0, 0));
+
+        for attachment in color_attachments.iter().chain(depth_attachment.iter()) {
+            if attachment.size() != size {
+                return Err(FramebufferError::SizeMismatch {
+                    size,
+                    attachment_size: attachment.size(),
+                });
+            }
+        }
+        for attachment in &color_attachments {
+            if attachment.format().is_depth() {
+                return Err(FramebufferError::WrongAttachmentFormat(attachment.format()));
+            }
+        }
+        if let Some(attachment) = &depth_attachment {
+            if !attachment.format().is_depth() {
+                return Err(FramebufferError::WrongAttachmentFormat(attachment.format()));
+            }
+        }
+
+        let mut gl_handle: types::GLuint = 0;
+        unsafe {
+            gl.GenFramebuffers(1, &mut gl_handle as *mut _);
+        }
+        assert!(gl_handle > 0);
+
+        // Constructed before attaching so that Drop cleans up if the framebuffer is incomplete
+        let res = Self {
+            gl_handle,
+            size,
+            color_attachments,
+            depth_attachment,
+            gl,
+        };
+
+        let previous = FramebufferBinding::current(&res.gl);
+        unsafe {
+            res.gl.BindFramebuffer(FRAMEBUFFER, gl_handle);
+        }
+
+        let mut draw_buffers = Vec::new();
+        for (i, attachment) in res.color_attachments.iter().enumerate() {
+            let attachment_point = COLOR_ATTACHMENT0 + i as u32;
+            attachment.attach(attachment_point, &res.gl);
+            draw_buffers.push(attachment_point);
+        }
+        if let Some(attachment) = &res.depth_attachment {
+            let attachment_point = if attachment.format() == TextureFormat::Depth24Stencil8 {
+                DEPTH_STENCIL_ATTACHMENT
+            } else {
+                DEPTH_ATTACHMENT
+            };
+            attachment.attach(attachment_point, &res.gl);
+        }
+
+        let status = unsafe {
+            if draw_buffers.is_empty() {
+                res.gl.DrawBuffers(1, &NONE as *const u32);
+                res.gl.ReadBuffer(NONE);
+            } else {
+                res.gl
+                    .DrawBuffers(draw_buffers.len() as i32, draw_buffers.as_ptr());
+            }
+            res.gl.CheckFramebufferStatus(FRAMEBUFFER)
+        };
+        previous.restore(&res.gl);
+
+        if status != FRAMEBUFFER_COMPLETE {
+            return Err(FramebufferError::Incomplete(status));
+        }
+        Ok(res)
+    }
+    /// A framebuffer rendering into a color texture, optionally with a depth renderbuffer
+    pub fn with_color_texture(
+        size: glm::UVec2,
+        color_format: TextureFormat,
+        depth_format: Option<TextureFormat>,
+        gl: std::rc::Rc<Gl>,
+    ) -> Result<Self, FramebufferError> {
+        let color = Texture2D::new(size, color_format, false, gl.clone())?;
+        let depth = depth_format
+            .map(|format| Renderbuffer::new(size, format, 0, gl.clone()))
+            .map(FramebufferAttachment::Renderbuffer);
+
+        Self::new(vec![FramebufferAttachment::Texture(color)], depth, gl)
+    }
+    /// A multisampled framebuffer backed by renderbuffers. Its content has to be resolved into a
+    /// single sampled framebuffer with `blit_to` before it can be sampled
+    pub fn multisampled(
+        size: glm::UVec2,
+        color_format: TextureFormat,
+        depth_format: Option<TextureFormat>,
+        samples: i32,
+        gl: std::rc::Rc<Gl>,
+    ) -> Result<Self, FramebufferError> {
+        // Asking for more samples than supported is an error, so clamp to the maximum
+        let mut max_samples: types::GLint = 0;
+        unsafe {
+            gl.GetIntegerv(MAX_SAMPLES, &mut max_samples as *mut i32);
+        }
+        let samples = samples.min(max_samples);
+
+        let color = Renderbuffer::new(size, color_format, samples, gl.clone());
+        let depth = depth_format
+            .map(|format| Renderbuffer::new(size, format, samples, gl.clone()))
+            .map(FramebufferAttachment::Renderbuffer);
+
+        Self::new(vec![FramebufferAttachment::Renderbuffer(color)], depth, gl)
+    }
+
+    pub fn gl_handle(&self) -> types::GLuint {
+        self.gl_handle
+    }
+    pub fn size(&self) -> glm::UVec2 {
+        self.size
+    }
+    pub fn is_multisampled(&self) -> bool {
+        self.color_attachments
+            .iter()
+            .chain(self.depth_attachment.iter())
+            .any(|attachment| match attachment {
+                FramebufferAttachment::Renderbuffer(renderbuffer) => renderbuffer.samples() > 0,
+                FramebufferAttachment::Texture(_) => false,
+            })
+    }
+    /// The texture of a color attachment, if it is one
+    pub fn color_texture(&self, index: usize) -> Option<&Texture2D> {
+        match self.color_attachments.get(index) {
+            Some(FramebufferAttachment::Texture(texture)) => Some(texture),
+            _ => None,
+        }
+    }
+    pub fn depth_texture(&self) -> Option<&Texture2D> {
+        match &self.depth_attachment {
+            Some(FramebufferAttachment::Texture(texture)) => Some(texture),
+            _ => None,
+        }
+    }
+    /// Makes the framebuffer the render target and sets the viewport to cover it. Returns the
+    /// previous state so it can be restored afterwards
+    pub fn bind(&self, gl: &super::Gl) -> FramebufferBinding {
+        let previous = FramebufferBinding::current(gl);
+        unsafe {
+            gl.BindFramebuffer(FRAMEBUFFER, self.gl_handle);
+        }
+        resize_viewport(&self.size, gl);
+        previous
+    }
+    /// Binds the framebuffer for the duration of `render`
+    pub fn render_to<R>(&self, gl: &super::Gl, render: impl FnOnce() -> R) -> R {
+        let previous = self.bind(gl);
+        let res = render();
+        previous.restore(gl);
+        res
+    }
+    /// Copies the content of the framebuffer into another one, scaling it to the size of
+    /// `target`. `mask` selects the buffers, i.e. COLOR_BUFFER_BIT. This is also how multisampled
+    /// framebuffers are resolved, which requires both to be the same size
+    pub fn blit_to(
+        &self,
+        target: &Framebuffer,
+        mask: types::GLbitfield,
+        gl: &super::Gl,
+    ) -> Result<(), FramebufferError> {
+        self.blit(target.gl_handle, target.size, mask, gl)
+    }
+    /// Like `blit_to`, but copies into the window, whose drawable size is `window_size`
+    pub fn blit_to_window(
+        &self,
+        window_size: glm::UVec2,
+        mask: types::GLbitfield,
+        gl: &super::Gl,
+    ) -> Result<(), FramebufferError> {
+        self.blit(0, window_size, mask, gl)
+    }
+    fn blit(
+        &self,
+        target_gl_handle: types::GLuint,
+        target_size: glm::UVec2,
+        mask: types::GLbitfield,
+        gl: &super::Gl,
+    ) -> Result<(), FramebufferError> {
+        if self.is_multisampled() && target_size != self.size {
+            return Err(FramebufferError::ResolveSizeMismatch);
+        }
+
+        // Depth and stencil can only be blitted with nearest filtering
+        let filter = if mask == COLOR_BUFFER_BIT && target_size != self.size {
+            LINEAR
+        } else {
+            NEAREST
+        };
+
+        let previous = FramebufferBinding::current(gl);
+        unsafe {
+            gl.BindFramebuffer(READ_FRAMEBUFFER, self.gl_handle);
+            gl.BindFramebuffer(DRAW_FRAMEBUFFER, target_gl_handle);
+            gl.BlitFramebuffer(
+                0,
+                0,
+                self.size.x as i32,
+                self.size.y as i32,
+                0,
+                0,
+                target_size.x as i32,
+                target_size.y as i32,
+                mask,
+                filter,
+            );
+        }
+        previous.restore(gl);
+        Ok(())
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.DeleteFramebuffers(1, &self.gl_handle as *const _);
+        }
+    }
+}
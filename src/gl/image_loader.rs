@@ -0,0 +1,147 @@
+use super::*;
+use std::path::{Path, PathBuf};
+
+/// How an image file is turned into texture data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub struct ImageLoadOptions {
+    /// Flip the rows so that the first row in memory is the bottom of the image, which is where
+    /// GL expects the origin of texture coordinates
+    pub flip_vertically: bool,
+    /// Multiply the color channels with alpha, for blending with ONE, ONE_MINUS_SRC_ALPHA
+    pub premultiply_alpha: bool,
+    /// Treat the color channels as sRGB so that sampling returns linear values. Only applies to
+    /// images with color channels
+    pub srgb: bool,
+    pub mipmaps: bool,
+}
+
+impl Default for ImageLoadOptions {
+    fn default() -> Self {
+        Self {
+            flip_vertically: false,
+            premultiply_alpha: false,
+            srgb: false,
+            mipmaps: true,
+        }
+    }
+}
+
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum ImageError {
+    FileUnreadable(PathBuf, std::io::Error),
+    /// The data is not a PNG, JPEG or TGA image, or is corrupt
+    DecodeFailed(image::ImageError),
+    Texture(TextureError),
+}
+
+impl std::error::Error for ImageError {}
+impl std::fmt::Display for ImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let to_write = match self {
+            ImageError::FileUnreadable(path, e) => {
+                std::format!("Could not read image file '{}': {}", path.display(), e)
+            }
+            ImageError::DecodeFailed(e) => std::format!("Could not decode image: {}", e),
+            ImageError::Texture(e) => std::format!("Could not create texture from image: {}", e),
+        };
+        write!(f, "{}", to_write)
+    }
+}
+
+impl From<TextureError> for ImageError {
+    fn from(e: TextureError) -> Self {
+        ImageError::Texture(e)
+    }
+}
+
+/// Pixel data decoded from an image file, in a format that can be uploaded as is
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct DecodedImage {
+    pub size: glm::UVec2,
+    pub format: TextureFormat,
+    pub data: Vec<u8>,
+}
+
+/// Reads and decodes a PNG, JPEG or TGA file. The file is read with `std::fs`, so under
+/// emscripten it has to be embedded with `--embed-file` like `title.txt`
+#[allow(dead_code)]
+pub fn load_image(
+    path: impl AsRef<Path>,
+    options: &ImageLoadOptions,
+) -> Result<DecodedImage, ImageError> {
+    let path = path.as_ref();
+    let bytes =
+        std::fs::read(path).map_err(|e| ImageError::FileUnreadable(path.to_path_buf(), e))?;
+    decode_image(&bytes, options)
+}
+
+/// Decodes a PNG, JPEG or TGA image held in memory
+#[allow(dead_code)]
+pub fn decode_image(bytes: &[u8], options: &ImageLoadOptions) -> Result<DecodedImage, ImageError> {
+    let mut image = image::load_from_memory(bytes).map_err(ImageError::DecodeFailed)?;
+
+    if options.flip_vertically {
+        image = image.flipv();
+    }
+
+    let size = glm::vec2(image.width(), image.height());
+
+    // Everything is widened to 8 bit RGB(A), so that grayscale samples as gray instead of red.
+    // There is no three channel sRGB format shared by both profiles, so sRGB color gets an alpha
+    // channel
+    let (format, mut data, has_alpha) = match image {
+        image::DynamicImage::ImageLuma8(_) | image::DynamicImage::ImageRgb8(_) if !options.srgb => {
+            (TextureFormat::RGB8, image.into_rgb8().into_raw(), false)
+        }
+        image => {
+            let format = if options.srgb {
+                TextureFormat::SRGB8Alpha8
+            } else {
+                TextureFormat::RGBA8
+            };
+            (format, image.into_rgba8().into_raw(), true)
+        }
+    };
+
+    if options.premultiply_alpha && has_alpha {
+        let channel_count = format.bytes_per_pixel();
+        for pixel in data.chunks_exact_mut(channel_count) {
+            let (color, alpha) = pixel.split_at_mut(channel_count - 1);
+            for channel in color {
+                *channel = ((*channel as u32 * alpha[0] as u32 + 127) / 255) as u8;
+            }
+        }
+    }
+
+    Ok(DecodedImage { size, format, data })
+}
+
+impl Texture2D {
+    /// Loads an image file into a new texture, see `load_image`
+    #[allow(dead_code)]
+    pub fn from_file(
+        path: impl AsRef<Path>,
+        options: &ImageLoadOptions,
+        gl: std::rc::Rc<Gl>,
+    ) -> Result<Texture2D, ImageError> {
+        let image = load_image(path, options)?;
+        Self::from_image(&image, options.mipmaps, gl)
+    }
+    #[allow(dead_code)]
+    pub fn from_image(
+        image: &DecodedImage,
+        mipmaps: bool,
+        gl: std::rc::Rc<Gl>,
+    ) -> Result<Texture2D, ImageError> {
+        Ok(Texture2D::from_data(
+            image.size,
+            image.format,
+            &image.data,
+            mipmaps,
+            gl,
+        )?)
+    }
+}
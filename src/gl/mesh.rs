@@ -0,0 +1,572 @@
+use super::*;
+use std::f32::consts::PI;
+
+/// Vertex layout of all meshes
+#[repr(C)]
+pub struct MeshVertex {
+    pub position: glm::Vec3,
+    pub normal: glm::Vec3,
+    pub uv: glm::Vec2,
+    pub color: Color,
+}
+crate::impl_vertex!(MeshVertex {
+    position,
+    normal,
+    uv,
+    color
+});
+
+const WHITE: Color = Color {
+    r: 255,
+    g: 255,
+    b: 255,
+    a: 255,
+};
+
+#[derive(Debug)]
+pub enum MeshError {
+    /// An attribute vector does not have one entry per position
+    AttributeCountMismatch {
+        attribute: &'static str,
+        count: usize,
+        vertex_count: usize,
+    },
+    /// An index refers to a vertex past the end of `positions`
+    IndexOutOfRange {
+        index: u32,
+        vertex_count: usize,
+    },
+    Vao(VaoError),
+}
+
+impl std::error::Error for MeshError {}
+impl std::fmt::Display for MeshError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let to_write = match self {
+            MeshError::AttributeCountMismatch {
+                attribute,
+                count,
+                vertex_count,
+            } => std::format!(
+                "Mesh has {} {} for {} vertices",
+                count,
+                attribute,
+                vertex_count
+            ),
+            MeshError::IndexOutOfRange {
+                index,
+                vertex_count,
+            } => std::format!(
+                "Mesh index {} is out of range of {} vertices",
+                index,
+                vertex_count
+            ),
+            MeshError::Vao(e) => std::format!("Could not upload mesh: {}", e),
+        };
+        write!(f, "{}", to_write)
+    }
+}
+
+impl From<VaoError> for MeshError {
+    fn from(e: VaoError) -> Self {
+        MeshError::Vao(e)
+    }
+}
+
+/// Geometry of a mesh on the CPU side, as produced by the generators. All attribute vectors have
+/// one entry per vertex, except `colors` which may be left empty to make every vertex white
+#[derive(Debug, Clone, Default)]
+pub struct MeshData {
+    pub positions: Vec<glm::Vec3>,
+    pub normals: Vec<glm::Vec3>,
+    pub uvs: Vec<glm::Vec2>,
+    pub colors: Vec<Color>,
+    /// Triangle list indices. Empty for meshes drawn without indices
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+    /// Gives every vertex the same color
+    #[allow(dead_code)]
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.colors = vec![color; self.vertex_count()];
+        self
+    }
+    /// Checks that every attribute has one entry per vertex and every index refers to a vertex
+    pub fn validate(&self) -> Result<(), MeshError> {
+        let vertex_count = self.vertex_count();
+        let attribute_counts = [
+            ("normals", self.normals.len()),
+            ("uvs", self.uvs.len()),
+            ("colors", self.colors.len()),
+        ];
+        for (attribute, count) in attribute_counts.iter() {
+            let optional = *attribute == "colors" && *count == 0;
+            if *count != vertex_count && !optional {
+                return Err(MeshError::AttributeCountMismatch {
+                    attribute,
+                    count: *count,
+                    vertex_count,
+                });
+            }
+        }
+
+        if let Some(index) = self
+            .indices
+            .iter()
+            .find(|index| **index as usize >= vertex_count)
+        {
+            return Err(MeshError::IndexOutOfRange {
+                index: *index,
+                vertex_count,
+            });
+        }
+        Ok(())
+    }
+    /// Interleaves the attributes into vertices. Panics if the data does not pass `validate`
+    pub fn vertices(&self) -> Vec<MeshVertex> {
+        (0..self.vertex_count())
+            .map(|i| MeshVertex {
+                position: self.positions[i],
+                normal: self.normals[i],
+                uv: self.uvs[i],
+                color: self.colors.get(i).copied().unwrap_or(WHITE),
+            })
+            .collect()
+    }
+    fn push_vertex(&mut self, position: glm::Vec3, normal: glm::Vec3, uv: glm::Vec2) -> u32 {
+        self.positions.push(position);
+        self.normals.push(normal);
+        self.uvs.push(uv);
+        (self.positions.len() - 1) as u32
+    }
+
+    /// A rectangle in the XY plane centered on the origin, facing +Z
+    #[allow(dead_code)]
+    pub fn quad(size: glm::Vec2) -> Self {
+        let mut res = Self::default();
+        let half = size * 0.5;
+        let normal = glm::vec3(0.0, 0.0, 1.0);
+
+        for (x, y) in &[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+            res.push_vertex(
+                glm::vec3(-half.x + x * size.x, -half.y + y * size.y, 0.0),
+                normal,
+                glm::vec2(*x, *y),
+            );
+        }
+        res.indices = vec![0, 1, 2, 0, 2, 3];
+        res
+    }
+    /// A regular polygon in the XY plane centered on the origin, facing +Z. The first corner
+    /// points towards -Y
+    pub fn ngon(sides: u32, radius: f32) -> Self {
+        assert!(sides >= 3);
+        let mut res = Self::default();
+        let normal = glm::vec3(0.0, 0.0, 1.0);
+
+        let center = res.push_vertex(glm::vec3(0.0, 0.0, 0.0), normal, glm::vec2(0.5, 0.5));
+        for i in 0..sides {
+            let angle = -PI / 2.0 + i as f32 / sides as f32 * 2.0 * PI;
+            let direction = glm::vec2(angle.cos(), angle.sin());
+            res.push_vertex(
+                glm::vec3(direction.x * radius, direction.y * radius, 0.0),
+                normal,
+                direction * 0.5 + glm::vec2(0.5, 0.5),
+            );
+        }
+        for i in 0..sides {
+            res.indices
+                .extend_from_slice(&[center, 1 + i, 1 + (i + 1) % sides]);
+        }
+        res
+    }
+    /// A circle approximated by `segments` sides
+    #[allow(dead_code)]
+    pub fn circle(radius: f32, segments: u32) -> Self {
+        Self::ngon(segments, radius)
+    }
+    /// An axis aligned box centered on the origin. Every face has its own vertices so that the
+    /// normals are flat
+    #[allow(dead_code)]
+    pub fn cube(size: glm::Vec3) -> Self {
+        let mut res = Self::default();
+        let half = size * 0.5;
+
+        // Normal of each face along with the two axes spanning it
+        let faces = [
+            (
+                glm::vec3(1.0, 0.0, 0.0),
+                glm::vec3(0.0, 0.0, -1.0),
+                glm::vec3(0.0, 1.0, 0.0),
+            ),
+            (
+                glm::vec3(-1.0, 0.0, 0.0),
+                glm::vec3(0.0, 0.0, 1.0),
+                glm::vec3(0.0, 1.0, 0.0),
+            ),
+            (
+                glm::vec3(0.0, 1.0, 0.0),
+                glm::vec3(1.0, 0.0, 0.0),
+                glm::vec3(0.0, 0.0, -1.0),
+            ),
+            (
+                glm::vec3(0.0, -1.0, 0.0),
+                glm::vec3(1.0, 0.0, 0.0),
+                glm::vec3(0.0, 0.0, 1.0),
+            ),
+            (
+                glm::vec3(0.0, 0.0, 1.0),
+                glm::vec3(1.0, 0.0, 0.0),
+                glm::vec3(0.0, 1.0, 0.0),
+            ),
+            (
+                glm::vec3(0.0, 0.0, -1.0),
+                glm::vec3(-1.0, 0.0, 0.0),
+                glm::vec3(0.0, 1.0, 0.0),
+            ),
+        ];
+
+        for (normal, u_axis, v_axis) in &faces {
+            let first = res.vertex_count() as u32;
+            for (u, v) in &[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+                let corner = normal + u_axis * (u * 2.0 - 1.0) + v_axis * (v * 2.0 - 1.0);
+                res.push_vertex(corner.component_mul(&half), *normal, glm::vec2(*u, *v));
+            }
+            res.indices.extend_from_slice(&[
+                first,
+                first + 1,
+                first + 2,
+                first,
+                first + 2,
+                first + 3,
+            ]);
+        }
+        res
+    }
+    /// A sphere centered on the origin made of `rings` bands of latitude and `sectors` bands of
+    /// longitude
+    #[allow(dead_code)]
+    pub fn uv_sphere(radius: f32, rings: u32, sectors: u32) -> Self {
+        assert!(rings >= 2 && sectors >= 3);
+        let mut res = Self::default();
+
+        for ring in 0..=rings {
+            let v = ring as f32 / rings as f32;
+            let polar = v * PI;
+            for sector in 0..=sectors {
+                let u = sector as f32 / sectors as f32;
+                let azimuth = u * 2.0 * PI;
+                let normal = glm::vec3(
+                    polar.sin() * azimuth.cos(),
+                    polar.cos(),
+                    polar.sin() * azimuth.sin(),
+                );
+                res.push_vertex(normal * radius, normal, glm::vec2(u, v));
+            }
+        }
+
+        let row_length = sectors + 1;
+        for ring in 0..rings {
+            for sector in 0..sectors {
+                let a = ring * row_length + sector;
+                let b = a + row_length;
+                res.indices
+                    .extend_from_slice(&[a, a + 1, b, a + 1, b + 1, b]);
+            }
+        }
+        res
+    }
+    /// A flat grid in the XZ plane centered on the origin, facing +Y, with the given number of
+    /// cells along each axis
+    #[allow(dead_code)]
+    pub fn plane_grid(size: glm::Vec2, subdivisions: glm::UVec2) -> Self {
+        assert!(subdivisions.x >= 1 && subdivisions.y >= 1);
+        let mut res = Self::default();
+        let normal = glm::vec3(0.0, 1.0, 0.0);
+
+        for z in 0..=subdivisions.y {
+            let v = z as f32 / subdivisions.y as f32;
+            for x in 0..=subdivisions.x {
+                let u = x as f32 / subdivisions.x as f32;
+                res.push_vertex(
+                    glm::vec3((u - 0.5) * size.x, 0.0, (v - 0.5) * size.y),
+                    normal,
+                    glm::vec2(u, v),
+                );
+            }
+        }
+
+        let row_length = subdivisions.x + 1;
+        for z in 0..subdivisions.y {
+            for x in 0..subdivisions.x {
+                let a = z * row_length + x;
+                let b = a + row_length;
+                res.indices
+                    .extend_from_slice(&[a, b, a + 1, a + 1, b, b + 1]);
+            }
+        }
+        res
+    }
+    /// A closed cylinder along the Y axis centered on the origin
+    #[allow(dead_code)]
+    pub fn cylinder(radius: f32, height: f32, segments: u32) -> Self {
+        assert!(segments >= 3);
+        let mut res = Self::default();
+        let half_height = height * 0.5;
+
+        // Side, with a seam of duplicated vertices so that the uvs wrap
+        for i in 0..=segments {
+            let u = i as f32 / segments as f32;
+            let angle = u * 2.0 * PI;
+            let normal = glm::vec3(angle.cos(), 0.0, angle.sin());
+            res.push_vertex(
+                normal * radius + glm::vec3(0.0, half_height, 0.0),
+                normal,
+                glm::vec2(u, 0.0),
+            );
+            res.push_vertex(
+                normal * radius - glm::vec3(0.0, half_height, 0.0),
+                normal,
+                glm::vec2(u, 1.0),
+            );
+        }
+        for i in 0..segments {
+            let top = i * 2;
+            res.indices
+                .extend_from_slice(&[top, top + 2, top + 1, top + 1, top + 2, top + 3]);
+        }
+
+        // Caps
+        for &side in &[1.0f32, -1.0] {
+            let normal = glm::vec3(0.0, side, 0.0);
+            let center = res.push_vertex(normal * half_height, normal, glm::vec2(0.5, 0.5));
+            for i in 0..segments {
+                let angle = i as f32 / segments as f32 * 2.0 * PI;
+                let direction = glm::vec2(angle.cos(), angle.sin());
+                res.push_vertex(
+                    glm::vec3(
+                        direction.x * radius,
+                        side * half_height,
+                        direction.y * radius,
+                    ),
+                    normal,
+                    direction * 0.5 + glm::vec2(0.5, 0.5),
+                );
+            }
+            for i in 0..segments {
+                let a = center + 1 + i;
+                let b = center + 1 + (i + 1) % segments;
+                // Keep both caps facing outwards
+                if side > 0.0 {
+                    res.indices.extend_from_slice(&[center, b, a]);
+                } else {
+                    res.indices.extend_from_slice(&[center, a, b]);
+                }
+            }
+        }
+        res
+    }
+}
+
+/// Geometry on the GPU, ready to be drawn: the buffers, the Vao reading them, the primitive mode
+/// and the range to draw
+pub struct Mesh {
+    vbo: ArrayVbo,
+    vao: Vao,
+    mode: PrimitiveMode,
+    /// Range of indices to draw, or of vertices if the mesh has no indices
+    first: usize,
+    count: usize,
+}
+
+impl Mesh {
+    /// Uploads the mesh data and builds a Vao matching the attributes of the shader
+    pub fn new(data: &MeshData, shader: &Shader, gl: std::rc::Rc<Gl>) -> Result<Mesh, MeshError> {
+        let vbo = ArrayVbo::new(gl.clone());
+        let mut vao = Vao::from_vertex_buffer::<MeshVertex>(&vbo, shader, gl.clone())?;
+
+        if !data.indices.is_empty() {
+            vao.set_element_buffer(ElementBuffer::new(gl.clone()), &gl);
+        }
+
+        let mut res = Mesh {
+            vbo,
+            vao,
+            mode: PrimitiveMode::Triangles,
+            first: 0,
+            count: 0,
+        };
+        res.upload(data, &gl)?;
+        Ok(res)
+    }
+    /// Replaces the geometry of the mesh and resets the draw range to all of it. Meshes created
+    /// without indices cannot be given indices later, and the other way around
+    pub fn upload(&mut self, data: &MeshData, gl: &Gl) -> Result<(), MeshError> {
+        // Checked before touching the buffers so that the mesh stays intact on error
+        data.validate()?;
+        match (self.vao.element_buffer().is_some(), data.indices.is_empty()) {
+            (true, true) => return Err(VaoError::MissingIndices.into()),
+            (false, false) => return Err(VaoError::NoElementBuffer.into()),
+            _ => {}
+        }
+
+        self.vbo
+            .upload_array_vbo_vec(STATIC_DRAW, &data.vertices(), gl);
+        ArrayVbo::unbind(gl);
+
+        if data.indices.is_empty() {
+            self.count = data.vertex_count();
+        } else if data.vertex_count() <= u16::MAX as usize + 1 {
+            let indices: Vec<u16> = data.indices.iter().map(|i| *i as u16).collect();
+            self.vao.upload_indices(STATIC_DRAW, &indices, gl)?;
+            self.count = indices.len();
+        } else {
+            self.vao.upload_indices(STATIC_DRAW, &data.indices, gl)?;
+            self.count = data.indices.len();
+        }
+        self.first = 0;
+        Ok(())
+    }
+    #[allow(dead_code)]
+    pub fn vao(&self) -> &Vao {
+        &self.vao
+    }
+    #[allow(dead_code)]
+    pub fn mode(&self) -> PrimitiveMode {
+        self.mode
+    }
+    /// Changes how the mesh is assembled, i.e. to draw the indices as lines for an outline. The
+    /// indices have to make sense for the new mode
+    #[allow(dead_code)]
+    pub fn set_mode(&mut self, mode: PrimitiveMode) {
+        self.mode = mode;
+    }
+    /// Restricts drawing to `count` indices starting at `first`, or vertices if the mesh has no
+    /// indices
+    #[allow(dead_code)]
+    pub fn set_draw_range(&mut self, first: usize, count: usize) {
+        self.first = first;
+        self.count = count;
+    }
+    /// Draws the mesh with the currently bound shader
+    pub fn draw(&self, gl: &Gl) {
+        self.vao.bind(gl);
+        match self.vao.element_buffer() {
+            Some(element_buffer) => draw_elements(
+                self.mode,
+                self.count as i32,
+                element_buffer.index_type(),
+                self.first,
+                gl,
+            ),
+            None => draw_arrays_range(self.mode, self.first as i32, self.count as i32, gl),
+        }
+        Vao::unbind(gl);
+    }
+    /// Draws several ranges of the mesh at once, given as (first, count) of indices, or vertices
+    /// if the mesh has no indices. Ignores the draw range of the mesh
+    #[allow(dead_code)]
+    pub fn draw_ranges(&self, ranges: &[(usize, usize)], profile: GlProfile, gl: &Gl) {
+        self.vao.bind(gl);
+        match self.vao.element_buffer() {
+            Some(element_buffer) => {
+                let ranges: Vec<(usize, i32)> = ranges
+                    .iter()
+                    .map(|(first, count)| (*first, *count as i32))
+                    .collect();
+                multi_draw_elements(self.mode, element_buffer.index_type(), &ranges, profile, gl);
+            }
+            None => {
+                let ranges: Vec<(i32, i32)> = ranges
+                    .iter()
+                    .map(|(first, count)| (*first as i32, *count as i32))
+                    .collect();
+                multi_draw_arrays(self.mode, &ranges, profile, gl);
+            }
+        }
+        Vao::unbind(gl);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that every triangle is counter-clockwise when seen from the side its vertex normals
+    /// point to, so that back-face culling keeps the outside
+    fn assert_winding_matches_normals(name: &str, data: &MeshData) {
+        for triangle in data.indices.chunks_exact(3) {
+            let [a, b, c] = [
+                triangle[0] as usize,
+                triangle[1] as usize,
+                triangle[2] as usize,
+            ];
+            let face_normal = (data.positions[b] - data.positions[a])
+                .cross(&(data.positions[c] - data.positions[a]));
+            // Triangles collapsed at the poles of the sphere have no orientation
+            if face_normal.norm() < 1e-6 {
+                continue;
+            }
+            let vertex_normal = data.normals[a] + data.normals[b] + data.normals[c];
+            assert!(
+                face_normal.dot(&vertex_normal) > 0.0,
+                "{}: triangle {:?} winds against its normals",
+                name,
+                triangle
+            );
+        }
+    }
+
+    #[test]
+    fn generators_wind_counter_clockwise_around_normals() {
+        let meshes = [
+            ("quad", MeshData::quad(glm::vec2(2.0, 1.0))),
+            ("ngon", MeshData::ngon(5, 1.0)),
+            ("circle", MeshData::circle(1.0, 32)),
+            ("cube", MeshData::cube(glm::vec3(1.0, 2.0, 3.0))),
+            ("uv_sphere", MeshData::uv_sphere(1.0, 8, 12)),
+            (
+                "plane_grid",
+                MeshData::plane_grid(glm::vec2(4.0, 2.0), glm::vec2(4, 3)),
+            ),
+            ("cylinder", MeshData::cylinder(1.0, 2.0, 16)),
+        ];
+        for (name, data) in &meshes {
+            assert!(!data.indices.is_empty());
+            assert!(data.validate().is_ok(), "{}", name);
+            assert_winding_matches_normals(name, data);
+        }
+    }
+
+    #[test]
+    fn validate_rejects_mismatched_attributes_and_indices() {
+        let mut data = MeshData::ngon(4, 1.0);
+        data.uvs.pop();
+        assert!(matches!(
+            data.validate(),
+            Err(MeshError::AttributeCountMismatch {
+                attribute: "uvs",
+                count: 4,
+                vertex_count: 5,
+            })
+        ));
+
+        let mut data = MeshData::ngon(4, 1.0).with_color(WHITE);
+        data.colors.pop();
+        assert!(data.validate().is_err());
+
+        let mut data = MeshData::ngon(4, 1.0);
+        data.indices.push(5);
+        assert!(matches!(
+            data.validate(),
+            Err(MeshError::IndexOutOfRange {
+                index: 5,
+                vertex_count: 5,
+            })
+        ));
+    }
+}
@@ -3,17 +3,65 @@ include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
 use std::ffi::CString;
 
+#[allow(unused_imports)]
+pub use self::atlas::*;
+#[allow(unused_imports)]
+pub use self::buffer::*;
 pub use self::camera::*;
 pub use self::color::*;
+pub use self::diagnostics::*;
+pub use self::dialect::*;
+pub use self::ebo::*;
+pub use self::framebuffer::*;
+pub use self::image_loader::*;
+pub use self::mesh::*;
+#[allow(unused_imports)]
+pub use self::model::*;
+pub use self::preprocessor::*;
+pub use self::primitive_mode::*;
+pub use self::profile::*;
+pub use self::program_cache::*;
+pub use self::screenshot::*;
 pub use self::shader::*;
+pub use self::shader_builder::*;
+pub use self::shader_watcher::*;
+pub use self::std140::*;
+#[allow(unused_imports)]
+pub use self::stream_buffer::*;
+pub use self::texture::*;
+pub use self::ubo::*;
+pub use self::uniform::*;
 pub use self::vao::*;
 pub use self::vbo::*;
+pub use self::vertex::*;
 
+mod atlas;
+mod buffer;
 mod camera;
 mod color;
+mod diagnostics;
+mod dialect;
+mod ebo;
+mod framebuffer;
+mod image_loader;
+mod mesh;
+mod model;
+mod preprocessor;
+mod primitive_mode;
+mod profile;
+mod program_cache;
+mod screenshot;
 mod shader;
+mod shader_builder;
+mod shader_watcher;
+mod std140;
+mod stream_buffer;
+mod texture;
+mod ubo;
+mod uniform;
 mod vao;
 mod vbo;
+mod vertex;
 
 pub fn clear(c: &color::Color, gl: &Gl) {
     unsafe {
@@ -27,12 +75,196 @@ pub fn resize_viewport(size: &glm::UVec2, gl: &Gl) {
     }
 }
 
-pub fn draw_arrays(vertex_count: i32, gl: &Gl) {
+pub fn draw_arrays(mode: PrimitiveMode, vertex_count: i32, gl: &Gl) {
     unsafe {
-        gl.DrawArrays(TRIANGLES, 0, vertex_count);
+        gl.DrawArrays(mode.gl_enum(), 0, vertex_count);
     }
 }
 
+/// Draws `vertex_count` vertices starting at vertex `first_vertex`, i.e. the `first_element` of a
+/// `StreamAllocation`
+pub fn draw_arrays_range(mode: PrimitiveMode, first_vertex: i32, vertex_count: i32, gl: &Gl) {
+    unsafe {
+        gl.DrawArrays(mode.gl_enum(), first_vertex, vertex_count);
+    }
+}
+
+/// Draws `vertex_count` vertices `instance_count` times. Attributes with a divisor advance per
+/// instance instead of per vertex
+#[allow(dead_code)]
+pub fn draw_arrays_instanced(mode: PrimitiveMode, vertex_count: i32, instance_count: i32, gl: &Gl) {
+    unsafe {
+        gl.DrawArraysInstanced(mode.gl_enum(), 0, vertex_count, instance_count);
+    }
+}
+
+/// Draws `index_count` indices of the element buffer of the bound Vao, starting at index
+/// `first_index`
+pub fn draw_elements(
+    mode: PrimitiveMode,
+    index_count: i32,
+    index_type: types::GLenum,
+    first_index: usize,
+    gl: &Gl,
+) {
+    let offset = first_index * index_type_size(index_type);
+    unsafe {
+        gl.DrawElements(
+            mode.gl_enum(),
+            index_count,
+            index_type,
+            offset as *const types::GLvoid,
+        );
+    }
+}
+
+/// Like `draw_elements` but adds `base_vertex` to every index before fetching the vertex, which
+/// lets several meshes share one vertex buffer. Only available on the Core43 profile
+#[allow(dead_code)]
+pub fn draw_elements_base_vertex(
+    mode: PrimitiveMode,
+    index_count: i32,
+    index_type: types::GLenum,
+    first_index: usize,
+    base_vertex: i32,
+    profile: GlProfile,
+    gl: &Gl,
+) -> Result<(), VaoError> {
+    if profile != GlProfile::Core43 || !gl.DrawElementsBaseVertex.is_loaded() {
+        return Err(VaoError::BaseVertexUnsupported(profile));
+    }
+
+    let offset = first_index * index_type_size(index_type);
+    unsafe {
+        gl.DrawElementsBaseVertex(
+            mode.gl_enum(),
+            index_count,
+            index_type,
+            offset as *const types::GLvoid,
+            base_vertex,
+        );
+    }
+    Ok(())
+}
+
+/// Like `draw_elements` but draws the indices `instance_count` times
+#[allow(dead_code)]
+pub fn draw_elements_instanced(
+    mode: PrimitiveMode,
+    index_count: i32,
+    index_type: types::GLenum,
+    first_index: usize,
+    instance_count: i32,
+    gl: &Gl,
+) {
+    let offset = first_index * index_type_size(index_type);
+    unsafe {
+        gl.DrawElementsInstanced(
+            mode.gl_enum(),
+            index_count,
+            index_type,
+            offset as *const types::GLvoid,
+            instance_count,
+        );
+    }
+}
+
+/// Instanced version of `draw_elements_base_vertex`. Only available on the Core43 profile
+#[allow(dead_code)]
+pub fn draw_elements_instanced_base_vertex(
+    mode: PrimitiveMode,
+    index_count: i32,
+    index_type: types::GLenum,
+    first_index: usize,
+    instance_count: i32,
+    base_vertex: i32,
+    profile: GlProfile,
+    gl: &Gl,
+) -> Result<(), VaoError> {
+    if profile != GlProfile::Core43 || !gl.DrawElementsInstancedBaseVertex.is_loaded() {
+        return Err(VaoError::BaseVertexUnsupported(profile));
+    }
+
+    let offset = first_index * index_type_size(index_type);
+    unsafe {
+        gl.DrawElementsInstancedBaseVertex(
+            mode.gl_enum(),
+            index_count,
+            index_type,
+            offset as *const types::GLvoid,
+            instance_count,
+            base_vertex,
+        );
+    }
+    Ok(())
+}
+
+/// Draws several ranges of the bound vertex buffers, given as (first vertex, vertex count), in
+/// one call. Falls back to one draw call per range where multi-draw is unavailable
+#[allow(dead_code)]
+pub fn multi_draw_arrays(mode: PrimitiveMode, ranges: &[(i32, i32)], profile: GlProfile, gl: &Gl) {
+    if profile != GlProfile::Core43 || !gl.MultiDrawArrays.is_loaded() {
+        for (first_vertex, vertex_count) in ranges {
+            draw_arrays_range(mode, *first_vertex, *vertex_count, gl);
+        }
+        return;
+    }
+
+    let firsts: Vec<i32> = ranges.iter().map(|range| range.0).collect();
+    let counts: Vec<i32> = ranges.iter().map(|range| range.1).collect();
+    unsafe {
+        gl.MultiDrawArrays(
+            mode.gl_enum(),
+            firsts.as_ptr(),
+            counts.as_ptr(),
+            ranges.len() as i32,
+        );
+    }
+}
+
+/// Draws several ranges of the element buffer of the bound Vao, given as (first index, index
+/// count), in one call. Falls back to one draw call per range where multi-draw is unavailable
+#[allow(dead_code)]
+pub fn multi_draw_elements(
+    mode: PrimitiveMode,
+    index_type: types::GLenum,
+    ranges: &[(usize, i32)],
+    profile: GlProfile,
+    gl: &Gl,
+) {
+    if profile != GlProfile::Core43 || !gl.MultiDrawElements.is_loaded() {
+        for (first_index, index_count) in ranges {
+            draw_elements(mode, *index_count, index_type, *first_index, gl);
+        }
+        return;
+    }
+
+    let offsets: Vec<*const types::GLvoid> = ranges
+        .iter()
+        .map(|range| (range.0 * index_type_size(index_type)) as *const types::GLvoid)
+        .collect();
+    let counts: Vec<i32> = ranges.iter().map(|range| range.1).collect();
+    unsafe {
+        gl.MultiDrawElements(
+            mode.gl_enum(),
+            counts.as_ptr(),
+            index_type,
+            offsets.as_ptr(),
+            ranges.len() as i32,
+        );
+    }
+}
+
+/// Makes writes done by shaders visible to the operations given by `barriers`, i.e.
+/// SHADER_STORAGE_BARRIER_BIT or VERTEX_ATTRIB_ARRAY_BARRIER_BIT. Needed after compute dispatches
+#[allow(dead_code)]
+pub fn memory_barrier(barriers: types::GLbitfield, gl: &Gl) {
+    unsafe {
+        gl.MemoryBarrier(barriers);
+    }
+}
+
+#[allow(dead_code)]
 pub fn get_uniform_location(shader: &Shader, name: &str, gl: &Gl) -> Result<i32, anyhow::Error> {
     let c_uniform_name = CString::new(name)?;
     let location =
@@ -0,0 +1,453 @@
+use super::*;
+use std::path::Path;
+
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum ModelError {
+    /// The file could not be read or is not valid glTF
+    Gltf(gltf::Error),
+    /// The file requires an extension the loader does not implement
+    UnsupportedExtension(String),
+    /// Sparse accessors are not supported
+    SparseAccessor(String),
+    /// An accessor without a buffer view, which would have to be filled with zeros
+    AccessorWithoutBufferView(String),
+    /// A vertex attribute with matrix dimensions
+    UnsupportedAttributeDimensions(String),
+    /// A buffer view that reaches past the end of its buffer
+    BufferViewOutOfRange {
+        view: usize,
+        end: usize,
+        buffer_length: usize,
+    },
+    Vao(VaoError),
+}
+
+impl std::error::Error for ModelError {}
+impl std::fmt::Display for ModelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let to_write = match self {
+            ModelError::Gltf(e) => std::format!("Could not load glTF file: {}", e),
+            ModelError::UnsupportedExtension(name) => {
+                std::format!("glTF file requires unsupported extension '{}'", name)
+            }
+            ModelError::SparseAccessor(attribute) => std::format!(
+                "Attribute '{}' uses a sparse accessor, which is not supported",
+                attribute
+            ),
+            ModelError::AccessorWithoutBufferView(attribute) => std::format!(
+                "Attribute '{}' uses an accessor without buffer view, which is not supported",
+                attribute
+            ),
+            ModelError::UnsupportedAttributeDimensions(attribute) => std::format!(
+                "Attribute '{}' has matrix dimensions, which vertex attributes cannot have",
+                attribute
+            ),
+            ModelError::BufferViewOutOfRange {
+                view,
+                end,
+                buffer_length,
+            } => std::format!(
+                "Buffer view {} ends at byte {} but its buffer is only {} bytes long",
+                view,
+                end,
+                buffer_length
+            ),
+            ModelError::Vao(e) => std::format!("Could not build Vao for glTF primitive: {}", e),
+        };
+        write!(f, "{}", to_write)
+    }
+}
+
+impl From<gltf::Error> for ModelError {
+    fn from(e: gltf::Error) -> Self {
+        ModelError::Gltf(e)
+    }
+}
+impl From<VaoError> for ModelError {
+    fn from(e: VaoError) -> Self {
+        ModelError::Vao(e)
+    }
+}
+
+/// The metallic-roughness material of a glTF primitive
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct Material {
+    pub name: Option<String>,
+    pub base_color_factor: glm::Vec4,
+    /// Index of the glTF texture holding the base color, along with the uv set it uses
+    pub base_color_texture: Option<(usize, u32)>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub metallic_roughness_texture: Option<(usize, u32)>,
+    pub normal_texture: Option<(usize, u32)>,
+    pub emissive_factor: glm::Vec3,
+    pub alpha_mode: gltf::material::AlphaMode,
+    pub alpha_cutoff: f32,
+    pub double_sided: bool,
+}
+
+/// One draw call worth of geometry
+#[allow(dead_code)]
+pub struct ModelPrimitive {
+    pub vao: Vao,
+    pub mode: PrimitiveMode,
+    /// Number of indices to draw, or of vertices if the primitive has no indices
+    pub count: usize,
+    /// Index into `Model::materials`, None for the default material
+    pub material: Option<usize>,
+}
+
+#[allow(dead_code)]
+impl ModelPrimitive {
+    /// Draws the primitive with the currently bound shader
+    pub fn draw(&self, gl: &Gl) {
+        self.vao.bind(gl);
+        match self.vao.element_buffer() {
+            Some(element_buffer) => draw_elements(
+                self.mode,
+                self.count as i32,
+                element_buffer.index_type(),
+                0,
+                gl,
+            ),
+            None => draw_arrays(self.mode, self.count as i32, gl),
+        }
+        Vao::unbind(gl);
+    }
+}
+
+#[allow(dead_code)]
+pub struct ModelMesh {
+    pub name: Option<String>,
+    pub primitives: Vec<ModelPrimitive>,
+}
+
+#[allow(dead_code)]
+pub struct ModelNode {
+    pub name: Option<String>,
+    /// Transform relative to the parent node
+    pub local_transform: glm::Mat4,
+    /// Index into `Model::meshes`
+    pub mesh: Option<usize>,
+    /// Indices into `Model::nodes`
+    pub children: Vec<usize>,
+}
+
+/// A glTF 2.0 model loaded onto the GPU
+#[allow(dead_code)]
+pub struct Model {
+    /// One buffer per glTF buffer view used by vertex attributes. Kept alive for the Vaos
+    vbos: Vec<ArrayVbo>,
+    pub meshes: Vec<ModelMesh>,
+    pub materials: Vec<Material>,
+    pub nodes: Vec<ModelNode>,
+    /// Nodes of the scene that have no parent
+    pub root_nodes: Vec<usize>,
+}
+
+#[allow(dead_code)]
+impl Model {
+    /// Loads a `.gltf` or `.glb` file. External buffers are looked up relative to the file
+    pub fn load(
+        path: impl AsRef<Path>,
+        shader: &Shader,
+        gl: std::rc::Rc<Gl>,
+    ) -> Result<Model, ModelError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|e| ModelError::Gltf(gltf::Error::Io(e)))?;
+        Self::from_slice(&bytes, path.parent(), shader, gl)
+    }
+    /// Loads a model from the contents of a `.gltf` or `.glb` file. Without `base_dir` only
+    /// embedded buffers can be used
+    pub fn from_slice(
+        bytes: &[u8],
+        base_dir: Option<&Path>,
+        shader: &Shader,
+        gl: std::rc::Rc<Gl>,
+    ) -> Result<Model, ModelError> {
+        let gltf = gltf::Gltf::from_slice(bytes)?;
+        let document = gltf.document;
+
+        if let Some(extension) = document.extensions_required().next() {
+            return Err(ModelError::UnsupportedExtension(String::from(extension)));
+        }
+
+        let buffer_data = gltf::import_buffers(&document, base_dir, gltf.blob)?;
+
+        // Every buffer view holding vertex data becomes an ArrayVbo, shared by all accessors
+        // pointing into it
+        let mut vbos = Vec::new();
+        let mut view_vbos = std::collections::HashMap::new();
+        for mesh in document.meshes() {
+            for primitive in mesh.primitives() {
+                for (_, accessor) in primitive.attributes() {
+                    let view = match accessor.view() {
+                        Some(view) => view,
+                        None => continue,
+                    };
+                    if view_vbos.contains_key(&view.index()) {
+                        continue;
+                    }
+
+                    // gltf only checks the length of the whole buffer, not each view
+                    let data = &buffer_data[view.buffer().index()].0;
+                    let end = view.offset().checked_add(view.length());
+                    let view_data = match end {
+                        Some(end) if end <= data.len() => &data[view.offset()..end],
+                        _ => {
+                            return Err(ModelError::BufferViewOutOfRange {
+                                view: view.index(),
+                                end: end.unwrap_or(usize::MAX),
+                                buffer_length: data.len(),
+                            })
+                        }
+                    };
+                    let vbo = ArrayVbo::new(gl.clone());
+                    vbo.upload_array_vbo_raw(STATIC_DRAW, view_data, &gl);
+                    vbos.push(vbo);
+                    view_vbos.insert(view.index(), vbos.len() - 1);
+                }
+            }
+        }
+        ArrayVbo::unbind(&gl);
+
+        let mut meshes = Vec::new();
+        for mesh in document.meshes() {
+            let mut primitives = Vec::new();
+            for primitive in mesh.primitives() {
+                primitives.push(load_primitive(
+                    &primitive,
+                    &buffer_data,
+                    &vbos,
+                    &view_vbos,
+                    shader,
+                    gl.clone(),
+                )?);
+            }
+            meshes.push(ModelMesh {
+                name: mesh.name().map(String::from),
+                primitives,
+            });
+        }
+
+        let materials = document.materials().map(|m| load_material(&m)).collect();
+
+        let nodes = document
+            .nodes()
+            .map(|node| ModelNode {
+                name: node.name().map(String::from),
+                // glTF matrices are column major, like glm
+                local_transform: glm::make_mat4(&node.transform().matrix().concat()),
+                mesh: node.mesh().map(|mesh| mesh.index()),
+                children: node.children().map(|child| child.index()).collect(),
+            })
+            .collect();
+
+        let root_nodes = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+            .map(|scene| scene.nodes().map(|node| node.index()).collect())
+            .unwrap_or_default();
+
+        Ok(Model {
+            vbos,
+            meshes,
+            materials,
+            nodes,
+            root_nodes,
+        })
+    }
+    /// The transform of every node relative to the model, indexed like `nodes`
+    pub fn world_transforms(&self) -> Vec<glm::Mat4> {
+        let mut transforms = vec![glm::Mat4::identity(); self.nodes.len()];
+        let mut stack: Vec<(usize, glm::Mat4)> = self
+            .root_nodes
+            .iter()
+            .map(|node| (*node, glm::Mat4::identity()))
+            .collect();
+
+        while let Some((node, parent_transform)) = stack.pop() {
+            let transform = parent_transform * self.nodes[node].local_transform;
+            transforms[node] = transform;
+            for child in &self.nodes[node].children {
+                stack.push((*child, transform));
+            }
+        }
+        transforms
+    }
+    /// Draws every mesh of the scene with the given shader, which has to be bound. Node
+    /// transforms are combined with `transform` and passed through `DEFAULT_MODEL_UNIFORM` if the
+    /// shader has it
+    pub fn draw(&self, transform: &glm::Mat4, shader: &Shader, gl: &Gl) -> Result<(), ShaderError> {
+        let world_transforms = self.world_transforms();
+        let mut stack = self.root_nodes.clone();
+
+        while let Some(node) = stack.pop() {
+            if let Some(mesh) = self.nodes[node].mesh {
+                let model = transform * world_transforms[node];
+                if shader.uniforms.contains_key(DEFAULT_MODEL_UNIFORM) {
+                    shader.set_uniform(DEFAULT_MODEL_UNIFORM, &model, gl)?;
+                }
+                for primitive in &self.meshes[mesh].primitives {
+                    primitive.draw(gl);
+                }
+            }
+            stack.extend_from_slice(&self.nodes[node].children);
+        }
+        Ok(())
+    }
+}
+
+/// The shader attribute name a glTF attribute is fed to. Matches the field names of `MeshVertex`
+pub fn gltf_attribute_name(semantic: &gltf::Semantic) -> String {
+    match semantic {
+        gltf::Semantic::Positions => String::from("position"),
+        gltf::Semantic::Normals => String::from("normal"),
+        gltf::Semantic::Tangents => String::from("tangent"),
+        gltf::Semantic::Colors(0) => String::from("color"),
+        gltf::Semantic::Colors(set) => std::format!("color{}", set),
+        gltf::Semantic::TexCoords(0) => String::from("uv"),
+        gltf::Semantic::TexCoords(set) => std::format!("uv{}", set),
+        gltf::Semantic::Joints(0) => String::from("joints"),
+        gltf::Semantic::Joints(set) => std::format!("joints{}", set),
+        gltf::Semantic::Weights(0) => String::from("weights"),
+        gltf::Semantic::Weights(set) => std::format!("weights{}", set),
+    }
+}
+
+/// Maps the accessors of a primitive onto attribute definitions reading straight from the buffer
+/// view data
+fn load_primitive(
+    primitive: &gltf::Primitive,
+    buffer_data: &[gltf::buffer::Data],
+    vbos: &[ArrayVbo],
+    view_vbos: &std::collections::HashMap<usize, usize>,
+    shader: &Shader,
+    gl: std::rc::Rc<Gl>,
+) -> Result<ModelPrimitive, ModelError> {
+    let mut pointer_definitions = Vec::new();
+    let mut vertex_count = 0;
+
+    for (semantic, accessor) in primitive.attributes() {
+        let name = gltf_attribute_name(&semantic);
+
+        if accessor.sparse().is_some() {
+            return Err(ModelError::SparseAccessor(name));
+        }
+        let view = accessor
+            .view()
+            .ok_or_else(|| ModelError::AccessorWithoutBufferView(name.clone()))?;
+        let dimension_count = match accessor.dimensions() {
+            gltf::accessor::Dimensions::Scalar => 1,
+            gltf::accessor::Dimensions::Vec2 => 2,
+            gltf::accessor::Dimensions::Vec3 => 3,
+            gltf::accessor::Dimensions::Vec4 => 4,
+            _ => return Err(ModelError::UnsupportedAttributeDimensions(name)),
+        };
+        vertex_count = accessor.count();
+
+        // Attributes the shader does not read are left out
+        let shader_attribute = match shader.attributes.get(&name) {
+            Some(shader_attribute) => shader_attribute,
+            None => continue,
+        };
+
+        // Integer data is converted for float attributes, but float data cannot feed integer ones
+        let data_type = accessor.data_type().as_gl_enum();
+        let integer = is_integer_attribute_type(shader_attribute.data_type);
+        if integer && !is_integer_vertex_data(data_type, accessor.normalized()) {
+            return Err(VaoError::AttributeTypeMismatch {
+                name,
+                data_type,
+                shader_data_type: shader_attribute.data_type,
+            }
+            .into());
+        }
+
+        pointer_definitions.push(VertexAttribPointerDefinition::new(
+            &vbos[view_vbos[&view.index()]],
+            shader_attribute.location as u32,
+            VertexAttributeDefinition {
+                dimension_count,
+                data_type,
+                normalized: accessor.normalized(),
+                integer,
+                stride: view.stride().unwrap_or(0) as u32,
+                offset: accessor.offset(),
+                divisor: 0,
+            },
+        ));
+    }
+
+    for shader_attribute in shader.attributes.values() {
+        let provided = primitive
+            .attributes()
+            .any(|(semantic, _)| gltf_attribute_name(&semantic) == shader_attribute.name);
+        if !provided {
+            return Err(VaoError::MissingAttribute(shader_attribute.name.clone()).into());
+        }
+    }
+
+    let mut vao = Vao::new(&pointer_definitions, gl.clone())?;
+
+    let reader = primitive.reader(|buffer| Some(&buffer_data[buffer.index()].0[..]));
+    let count = match reader.read_indices() {
+        Some(indices) => {
+            vao.set_element_buffer(ElementBuffer::new(gl.clone()), &gl);
+            match indices {
+                gltf::mesh::util::ReadIndices::U8(indices) => {
+                    let indices: Vec<u8> = indices.collect();
+                    vao.upload_indices(STATIC_DRAW, &indices, &gl)?;
+                    indices.len()
+                }
+                gltf::mesh::util::ReadIndices::U16(indices) => {
+                    let indices: Vec<u16> = indices.collect();
+                    vao.upload_indices(STATIC_DRAW, &indices, &gl)?;
+                    indices.len()
+                }
+                gltf::mesh::util::ReadIndices::U32(indices) => {
+                    let indices: Vec<u32> = indices.collect();
+                    vao.upload_indices(STATIC_DRAW, &indices, &gl)?;
+                    indices.len()
+                }
+            }
+        }
+        None => vertex_count,
+    };
+
+    Ok(ModelPrimitive {
+        vao,
+        //unwrap since glTF only has modes that GL has as well
+        mode: PrimitiveMode::from_gl_enum(primitive.mode().as_gl_enum()).unwrap(),
+        count,
+        material: primitive.material().index(),
+    })
+}
+
+fn load_material(material: &gltf::Material) -> Material {
+    let pbr = material.pbr_metallic_roughness();
+    let [r, g, b, a] = pbr.base_color_factor();
+    let [er, eg, eb] = material.emissive_factor();
+
+    Material {
+        name: material.name().map(String::from),
+        base_color_factor: glm::vec4(r, g, b, a),
+        base_color_texture: pbr
+            .base_color_texture()
+            .map(|info| (info.texture().index(), info.tex_coord())),
+        metallic_factor: pbr.metallic_factor(),
+        roughness_factor: pbr.roughness_factor(),
+        metallic_roughness_texture: pbr
+            .metallic_roughness_texture()
+            .map(|info| (info.texture().index(), info.tex_coord())),
+        normal_texture: material
+            .normal_texture()
+            .map(|info| (info.texture().index(), info.tex_coord())),
+        emissive_factor: glm::vec3(er, eg, eb),
+        alpha_mode: material.alpha_mode(),
+        alpha_cutoff: material.alpha_cutoff().unwrap_or(0.5),
+        double_sided: material.double_sided(),
+    }
+}
@@ -0,0 +1,324 @@
+use super::*;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Set of preprocessor definitions injected into shader sources. Ordered so that two equal sets
+/// hash the same, which lets them be used as keys for shader variants
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ShaderDefines {
+    defines: BTreeMap<String, String>,
+}
+
+impl ShaderDefines {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    /// Adds a valued definition, i.e. `#define LIGHT_COUNT 4`
+    #[allow(dead_code)]
+    pub fn with(mut self, name: &str, value: &str) -> Self {
+        self.defines.insert(String::from(name), String::from(value));
+        self
+    }
+    /// Adds a flag definition, i.e. `#define TEXTURED`
+    #[allow(dead_code)]
+    pub fn with_flag(self, name: &str) -> Self {
+        self.with(name, "")
+    }
+    pub fn insert(&mut self, name: &str, value: &str) {
+        self.defines.insert(String::from(name), String::from(value));
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.defines.iter()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.defines.is_empty()
+    }
+}
+
+/// Result of running the preprocessor on a shader source
+#[derive(Debug, Clone)]
+pub struct PreprocessedSource {
+    pub source: String,
+    /// Display names of all sources that went into the result. The index of a name is the
+    /// source string number used in the `#line` directives, which drivers report in their logs
+    pub source_names: Vec<String>,
+    /// Files that were read from disk, for watching them for changes
+    pub dependencies: Vec<PathBuf>,
+}
+
+/// Resolves `#include "file"` directives relative to `include_dir` and injects `defines` right
+/// after the `#version` directive. `#line` directives are emitted around included code so that
+/// line numbers in driver logs stay meaningful
+pub fn preprocess(
+    source: &str,
+    source_name: &str,
+    include_dir: &Path,
+    defines: &ShaderDefines,
+) -> Result<PreprocessedSource, ShaderError> {
+    let mut res = PreprocessedSource {
+        source: String::with_capacity(source.len()),
+        source_names: vec![String::from(source_name)],
+        dependencies: Vec::new(),
+    };
+
+    // The version directive has to come before any code, so the defines go right after it
+    let version_line = find_version_directive(source);
+    let head_length: usize = source
+        .split_inclusive('\n')
+        .take(version_line.map_or(0, |line| line + 1))
+        .map(str::len)
+        .sum();
+    let (head, body) = source.split_at(head_length);
+    let first_body_line = version_line.map_or(1, |line| line + 2);
+
+    res.source.push_str(head);
+    if !head.is_empty() && !head.ends_with('\n') {
+        res.source.push('\n');
+    }
+
+    for (name, value) in defines.iter() {
+        res.source
+            .push_str(&std::format!("#define {} {}\n", name, value));
+    }
+
+    if !defines.is_empty() {
+        res.source
+            .push_str(&std::format!("#line {} 0\n", first_body_line));
+    }
+
+    let mut include_stack = Vec::new();
+    expand_includes(
+        body,
+        0,
+        first_body_line,
+        include_dir,
+        &mut include_stack,
+        &mut res,
+    )?;
+
+    Ok(res)
+}
+
+fn expand_includes(
+    source: &str,
+    source_index: usize,
+    first_line: usize,
+    include_dir: &Path,
+    include_stack: &mut Vec<PathBuf>,
+    res: &mut PreprocessedSource,
+) -> Result<(), ShaderError> {
+    let mut in_block_comment = false;
+    // Nesting depth of conditionals while inside an `#if 0` block, 0 outside of one
+    let mut disabled_depth = 0usize;
+
+    for (line_index, line) in source.lines().enumerate() {
+        let code = strip_comments(line, &mut in_block_comment);
+        let directive = code.trim_start();
+
+        if disabled_depth > 0 {
+            if directive.starts_with("#if") {
+                disabled_depth += 1;
+            } else if directive.starts_with("#endif") {
+                disabled_depth -= 1;
+            } else if disabled_depth == 1
+                && (directive.starts_with("#else")
+                    || (directive.starts_with("#elif") && !is_if_zero(directive)))
+            {
+                disabled_depth = 0;
+            }
+        } else if is_if_zero(directive) {
+            disabled_depth = 1;
+        }
+
+        if disabled_depth > 0 || !directive.starts_with("#include") {
+            res.source.push_str(line);
+            res.source.push('\n');
+            continue;
+        }
+
+        let file_name = parse_include(directive)
+            .ok_or_else(|| ShaderError::MalformedInclude(String::from(line)))?;
+        let path = include_dir.join(file_name);
+
+        if include_stack.contains(&path) {
+            return Err(ShaderError::IncludeCycle(path));
+        }
+
+        let included_source = read_source_file(&path)?;
+        let included_index = res.source_names.len();
+        res.source_names.push(path.display().to_string());
+        if !res.dependencies.contains(&path) {
+            res.dependencies.push(path.clone());
+        }
+
+        res.source
+            .push_str(&std::format!("#line 1 {}\n", included_index));
+
+        include_stack.push(path);
+        expand_includes(
+            &included_source,
+            included_index,
+            1,
+            include_dir,
+            include_stack,
+            res,
+        )?;
+        include_stack.pop();
+
+        // Continue numbering the including file from the line after the directive
+        res.source.push_str(&std::format!(
+            "#line {} {}\n",
+            first_line + line_index + 1,
+            source_index
+        ));
+    }
+
+    Ok(())
+}
+
+/// Returns the index of the line holding the `#version` directive, which may only be preceded by
+/// comments and blank lines
+pub fn find_version_directive(source: &str) -> Option<usize> {
+    let mut in_block_comment = false;
+    for (line_index, line) in source.lines().enumerate() {
+        let code = strip_comments(line, &mut in_block_comment);
+        if code.trim().is_empty() {
+            continue;
+        }
+        return if is_version_directive(&code) {
+            Some(line_index)
+        } else {
+            None
+        };
+    }
+    None
+}
+
+fn is_version_directive(line: &str) -> bool {
+    line.trim_start().starts_with("#version")
+}
+
+/// True for `#if 0` and `#elif 0`
+fn is_if_zero(directive: &str) -> bool {
+    directive
+        .strip_prefix("#if")
+        .or_else(|| directive.strip_prefix("#elif"))
+        .map_or(false, |condition| condition.trim() == "0")
+}
+
+/// Returns the parts of the line outside of comments. `in_block_comment` carries whether a
+/// `/* */` comment is still open from one line to the next
+fn strip_comments(line: &str, in_block_comment: &mut bool) -> String {
+    let mut code = String::new();
+    let mut rest = line;
+    loop {
+        if *in_block_comment {
+            match rest.find("*/") {
+                Some(end) => {
+                    rest = &rest[end + 2..];
+                    *in_block_comment = false;
+                }
+                None => return code,
+            }
+        }
+
+        match (rest.find("//"), rest.find("/*")) {
+            (Some(line_comment), Some(block_comment)) if line_comment < block_comment => {
+                code.push_str(&rest[..line_comment]);
+                return code;
+            }
+            (Some(line_comment), None) => {
+                code.push_str(&rest[..line_comment]);
+                return code;
+            }
+            (_, Some(block_comment)) => {
+                // A comment separates tokens like whitespace does
+                code.push_str(&rest[..block_comment]);
+                code.push(' ');
+                rest = &rest[block_comment + 2..];
+                *in_block_comment = true;
+            }
+            (None, None) => {
+                code.push_str(rest);
+                return code;
+            }
+        }
+    }
+}
+
+/// Extracts the file name from `#include "file"` or `#include <file>`
+fn parse_include(line: &str) -> Option<&str> {
+    let argument = line.strip_prefix("#include")?.trim();
+
+    let file_name = argument
+        .strip_prefix('"')
+        .and_then(|a| a.strip_suffix('"'))
+        .or_else(|| argument.strip_prefix('<').and_then(|a| a.strip_suffix('>')))?;
+
+    if file_name.is_empty() {
+        None
+    } else {
+        Some(file_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preprocess_with_define(source: &str) -> String {
+        let defines = ShaderDefines::new().with_flag("TEXTURED");
+        preprocess(source, "test.vert", Path::new("missing"), &defines)
+            .unwrap()
+            .source
+    }
+
+    #[test]
+    fn defines_go_after_version_preceded_by_comments() {
+        let source =
+            "// License header\n/* spanning\n   lines */\n\n#version 300 es\nvoid main() {}\n";
+        let lines: Vec<String> = preprocess_with_define(source)
+            .lines()
+            .map(String::from)
+            .collect();
+
+        assert_eq!(lines[4], "#version 300 es");
+        assert_eq!(lines[5], "#define TEXTURED ");
+        assert_eq!(lines[6], "#line 6 0");
+        assert_eq!(lines[7], "void main() {}");
+    }
+
+    #[test]
+    fn includes_in_comments_and_disabled_blocks_are_kept_as_is() {
+        // Expanding any of these would fail, since the include directory does not exist
+        let source = "#version 300 es\n// #include \"a.glsl\"\n/*\n#include \"b.glsl\"\n*/\n#if 0\n#ifdef X\n#include \"c.glsl\"\n#endif\n#include \"d.glsl\"\n#elif 0\n#include \"e.glsl\"\n#endif\n";
+        let result = preprocess_with_define(source);
+
+        for file in &["a.glsl", "b.glsl", "c.glsl", "d.glsl", "e.glsl"] {
+            assert!(result.contains(&std::format!("#include \"{}\"", file)));
+        }
+    }
+
+    #[test]
+    fn includes_after_disabled_blocks_are_expanded() {
+        let sources = [
+            "#version 300 es\n#if 0\n#endif\n#include \"a.glsl\"\n",
+            "#version 300 es\n#if 0\n#elif 0\n#else\n#include \"a.glsl\"\n#endif\n",
+            "#version 300 es\n#if 0\n#elif defined(X)\n#include \"a.glsl\"\n#endif\n",
+        ];
+        for source in &sources {
+            // The include is attempted, which fails since the include directory does not exist
+            assert!(
+                preprocess(
+                    source,
+                    "test.vert",
+                    Path::new("missing"),
+                    &ShaderDefines::new()
+                )
+                .is_err(),
+                "{}",
+                source
+            );
+        }
+    }
+}
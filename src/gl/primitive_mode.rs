@@ -0,0 +1,46 @@
+use super::*;
+
+/// How the vertices of a draw call are assembled into primitives
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PrimitiveMode {
+    Points,
+    Lines,
+    LineStrip,
+    /// A line strip that also connects the last vertex to the first
+    LineLoop,
+    Triangles,
+    TriangleStrip,
+    TriangleFan,
+}
+
+impl PrimitiveMode {
+    pub fn gl_enum(&self) -> types::GLenum {
+        match self {
+            PrimitiveMode::Points => POINTS,
+            PrimitiveMode::Lines => LINES,
+            PrimitiveMode::LineStrip => LINE_STRIP,
+            PrimitiveMode::LineLoop => LINE_LOOP,
+            PrimitiveMode::Triangles => TRIANGLES,
+            PrimitiveMode::TriangleStrip => TRIANGLE_STRIP,
+            PrimitiveMode::TriangleFan => TRIANGLE_FAN,
+        }
+    }
+    pub fn from_gl_enum(mode: types::GLenum) -> Option<Self> {
+        match mode {
+            POINTS => Some(PrimitiveMode::Points),
+            LINES => Some(PrimitiveMode::Lines),
+            LINE_STRIP => Some(PrimitiveMode::LineStrip),
+            LINE_LOOP => Some(PrimitiveMode::LineLoop),
+            TRIANGLES => Some(PrimitiveMode::Triangles),
+            TRIANGLE_STRIP => Some(PrimitiveMode::TriangleStrip),
+            TRIANGLE_FAN => Some(PrimitiveMode::TriangleFan),
+            _ => None,
+        }
+    }
+}
+
+impl Default for PrimitiveMode {
+    fn default() -> Self {
+        PrimitiveMode::Triangles
+    }
+}
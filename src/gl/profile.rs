@@ -0,0 +1,51 @@
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlProfile {
+    Core43,
+    ES3,
+}
+
+impl GlProfile {
+    /// Determines the profile of the current context from its version string. This reflects what
+    /// the context actually ended up being, which is not necessarily what was asked for
+    pub fn detect(gl: &Gl) -> Self {
+        let version = get_string(VERSION, gl);
+
+        // ES contexts, including WebGL 2 under emscripten, report "OpenGL ES 3.0 ..."
+        if version.starts_with("OpenGL ES") {
+            GlProfile::ES3
+        } else {
+            GlProfile::Core43
+        }
+    }
+}
+
+/// Reads one of the strings from GetString such as VENDOR, RENDERER or VERSION
+pub fn get_string(name: types::GLenum, gl: &Gl) -> String {
+    unsafe {
+        let string = gl.GetString(name);
+
+        if string.is_null() {
+            String::new()
+        } else {
+            std::ffi::CStr::from_ptr(string as *const _)
+                .to_string_lossy()
+                .into_owned()
+        }
+    }
+}
+
+/// Checks whether the context exposes an extension, i.e. "GL_EXT_texture_filter_anisotropic"
+pub fn has_extension(name: &str, gl: &Gl) -> bool {
+    let mut count: types::GLint = 0;
+    unsafe {
+        gl.GetIntegerv(NUM_EXTENSIONS, &mut count as *mut i32);
+    }
+
+    (0..count as u32).any(|i| unsafe {
+        let extension = gl.GetStringi(EXTENSIONS, i);
+        !extension.is_null()
+            && std::ffi::CStr::from_ptr(extension as *const _).to_bytes() == name.as_bytes()
+    })
+}
@@ -0,0 +1,167 @@
+use super::*;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+
+/// Stores linked program binaries on disk so that later runs can skip compiling from source.
+/// Entries are keyed by everything that influences the binary, including the driver, so a driver
+/// update simply results in cache misses. Does nothing on contexts that cannot retrieve program
+/// binaries, such as WebGL
+#[derive(Debug, Clone)]
+pub struct ProgramBinaryCache {
+    dir: PathBuf,
+}
+
+impl ProgramBinaryCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn is_supported(gl: &Gl) -> bool {
+        if !gl.GetProgramBinary.is_loaded()
+            || !gl.ProgramBinary.is_loaded()
+            || !gl.ProgramParameteri.is_loaded()
+        {
+            return false;
+        }
+
+        let mut format_count: types::GLint = 0;
+        unsafe {
+            gl.GetIntegerv(NUM_PROGRAM_BINARY_FORMATS, &mut format_count as *mut i32);
+        }
+        format_count > 0
+    }
+
+    pub fn key(
+        &self,
+        sources: &[&str],
+        defines: &ShaderDefines,
+        attribute_bindings: &[ShaderAttributeBinding],
+        gl: &Gl,
+    ) -> u64 {
+        let mut hasher = Fnv1aHasher::new();
+
+        sources.hash(&mut hasher);
+        defines.hash(&mut hasher);
+        for binding in attribute_bindings {
+            binding.name.hash(&mut hasher);
+            binding.index.hash(&mut hasher);
+        }
+        get_string(VENDOR, gl).hash(&mut hasher);
+        get_string(RENDERER, gl).hash(&mut hasher);
+        get_string(VERSION, gl).hash(&mut hasher);
+
+        hasher.finish()
+    }
+
+    /// Creates a program from the cached binary for the given key. Returns None if there is no
+    /// entry or if the driver rejects it, in which case the stale entry is removed
+    pub fn load(&self, key: u64, gl: &Gl) -> Option<types::GLuint> {
+        if !Self::is_supported(gl) {
+            return None;
+        }
+
+        let path = self.entry_path(key);
+        let data = std::fs::read(&path).ok()?;
+
+        if data.len() < 4 {
+            let _ = std::fs::remove_file(&path);
+            return None;
+        }
+
+        // Entries start with the binary format as a little endian u32
+        let (format, binary) = data.split_at(4);
+        let format = u32::from_le_bytes([format[0], format[1], format[2], format[3]]);
+
+        let program_gl_handle = unsafe { gl.CreateProgram() };
+        let mut success: types::GLint = 0;
+        unsafe {
+            gl.ProgramBinary(
+                program_gl_handle,
+                format,
+                binary.as_ptr() as *const _,
+                binary.len() as i32,
+            );
+            gl.GetProgramiv(program_gl_handle, LINK_STATUS, &mut success as *mut i32);
+        }
+
+        if success as u8 == FALSE {
+            unsafe { gl.DeleteProgram(program_gl_handle) };
+            let _ = std::fs::remove_file(&path);
+            return None;
+        }
+
+        Some(program_gl_handle)
+    }
+
+    /// Writes the binary of a linked program to the cache. The program should have been linked
+    /// with PROGRAM_BINARY_RETRIEVABLE_HINT set. Failing to write is not fatal, so it is only
+    /// reported
+    pub fn store(&self, key: u64, program_gl_handle: types::GLuint, gl: &Gl) {
+        if !Self::is_supported(gl) {
+            return;
+        }
+
+        let mut length: types::GLint = 0;
+        unsafe {
+            gl.GetProgramiv(
+                program_gl_handle,
+                PROGRAM_BINARY_LENGTH,
+                &mut length as *mut i32,
+            );
+        }
+
+        if length <= 0 {
+            return;
+        }
+
+        let mut binary: Vec<u8> = vec![0; length as usize];
+        let mut format: types::GLenum = 0;
+        unsafe {
+            gl.GetProgramBinary(
+                program_gl_handle,
+                length,
+                &mut length as *mut i32,
+                &mut format as *mut u32,
+                binary.as_mut_ptr() as *mut _,
+            );
+        }
+        binary.truncate(length as usize);
+
+        let mut data = Vec::with_capacity(binary.len() + 4);
+        data.extend_from_slice(&format.to_le_bytes());
+        data.extend_from_slice(&binary);
+
+        let result = std::fs::create_dir_all(&self.dir)
+            .and_then(|_| std::fs::write(self.entry_path(key), data));
+
+        if let Err(e) = result {
+            eprintln!("Could not write program binary cache entry: {}", e);
+        }
+    }
+
+    fn entry_path(&self, key: u64) -> PathBuf {
+        self.dir.join(std::format!("{:016x}.bin", key))
+    }
+}
+
+/// 64 bit FNV-1a. Used for cache keys because they name files that outlive the build, while the
+/// algorithm of `DefaultHasher` may change between Rust releases
+struct Fnv1aHasher(u64);
+
+impl Fnv1aHasher {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1aHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
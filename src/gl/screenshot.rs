@@ -0,0 +1,145 @@
+use super::*;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum ScreenshotError {
+    CreateDirectoryFailed(PathBuf, std::io::Error),
+    EncodeFailed(PathBuf, image::ImageError),
+    /// The screenshot has no pixels, i.e. it was taken of a minimized window
+    Empty,
+}
+
+impl std::error::Error for ScreenshotError {}
+impl std::fmt::Display for ScreenshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let to_write = match self {
+            ScreenshotError::CreateDirectoryFailed(path, e) => std::format!(
+                "Could not create screenshot directory '{}': {}",
+                path.display(),
+                e
+            ),
+            ScreenshotError::EncodeFailed(path, e) => {
+                std::format!("Could not write screenshot '{}': {}", path.display(), e)
+            }
+            ScreenshotError::Empty => String::from("Cannot save a screenshot without pixels"),
+        };
+        write!(f, "{}", to_write)
+    }
+}
+
+/// RGBA8 pixels read back from a framebuffer. The first row is the top of the image, unlike GL
+/// where it is the bottom
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screenshot {
+    pub size: glm::UVec2,
+    pub pixels: Vec<u8>,
+}
+
+impl Screenshot {
+    /// The RGBA value of the pixel at `position`, counted from the top left corner
+    #[allow(dead_code)]
+    pub fn pixel(&self, position: glm::UVec2) -> [u8; 4] {
+        let start = ((position.y * self.size.x + position.x) * 4) as usize;
+        let mut pixel = [0; 4];
+        pixel.copy_from_slice(&self.pixels[start..start + 4]);
+        pixel
+    }
+    pub fn is_empty(&self) -> bool {
+        self.size.x == 0 || self.size.y == 0
+    }
+    pub fn save_png(&self, path: impl AsRef<Path>) -> Result<(), ScreenshotError> {
+        if self.is_empty() {
+            return Err(ScreenshotError::Empty);
+        }
+        let path = path.as_ref();
+        image::save_buffer_with_format(
+            path,
+            &self.pixels,
+            self.size.x,
+            self.size.y,
+            image::ExtendedColorType::Rgba8,
+            image::ImageFormat::Png,
+        )
+        .map_err(|e| ScreenshotError::EncodeFailed(path.to_path_buf(), e))
+    }
+    /// Writes the screenshot into `directory`, creating it if needed, with a name made from the
+    /// current time. Returns the path of the written file
+    pub fn save_timestamped_png(
+        &self,
+        directory: impl AsRef<Path>,
+    ) -> Result<PathBuf, ScreenshotError> {
+        if self.is_empty() {
+            return Err(ScreenshotError::Empty);
+        }
+        let directory = directory.as_ref();
+        std::fs::create_dir_all(directory)
+            .map_err(|e| ScreenshotError::CreateDirectoryFailed(directory.to_path_buf(), e))?;
+
+        // A clock set before 1970 only affects the name
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        let path = directory.join(std::format!(
+            "screenshot_{}_{:03}.png",
+            timestamp.as_secs(),
+            timestamp.subsec_millis()
+        ));
+        self.save_png(&path)?;
+        Ok(path)
+    }
+}
+
+/// Reads back what has been rendered to the window. Call it after rendering and before swapping,
+/// since the back buffer is undefined after a swap
+pub fn read_window_pixels(size: glm::UVec2, gl: &Gl) -> Screenshot {
+    read_framebuffer_pixels(0, size, gl)
+}
+
+impl Framebuffer {
+    /// Reads back the first color attachment, which has to be single sampled
+    #[allow(dead_code)]
+    pub fn read_pixels(&self, gl: &super::Gl) -> Screenshot {
+        read_framebuffer_pixels(self.gl_handle(), self.size(), gl)
+    }
+}
+
+fn read_framebuffer_pixels(framebuffer: types::GLuint, size: glm::UVec2, gl: &Gl) -> Screenshot {
+    // The drawable size of a minimized window is 0
+    if size.x == 0 || size.y == 0 {
+        return Screenshot {
+            size,
+            pixels: Vec::new(),
+        };
+    }
+
+    let row_size = size.x as usize * 4;
+    let mut pixels = vec![0u8; row_size * size.y as usize];
+
+    let previous = FramebufferBinding::current(gl);
+    unsafe {
+        gl.BindFramebuffer(READ_FRAMEBUFFER, framebuffer);
+        gl.PixelStorei(PACK_ALIGNMENT, 1);
+        gl.ReadPixels(
+            0,
+            0,
+            size.x as i32,
+            size.y as i32,
+            RGBA,
+            UNSIGNED_BYTE,
+            pixels.as_mut_ptr() as *mut types::GLvoid,
+        );
+        gl.PixelStorei(PACK_ALIGNMENT, 4);
+    }
+    previous.restore(gl);
+
+    // GL returns the bottom row first
+    let mut flipped = Vec::with_capacity(pixels.len());
+    for row in pixels.chunks_exact(row_size).rev() {
+        flipped.extend_from_slice(row);
+    }
+
+    Screenshot {
+        size,
+        pixels: flipped,
+    }
+}
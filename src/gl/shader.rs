@@ -1,54 +1,236 @@
 pub use super::*;
 
 // shader
+#[allow(dead_code)]
 pub struct Shader {
     pub program_gl_handle: types::GLuint,
-    pub vertex_gl_handle: types::GLuint,
-    pub fragment_gl_handle: types::GLuint,
-    pub vertex_source: String,
-    pub fragment_source: String,
+    /// The stages the program was built from. Empty handles if it was loaded from a binary
+    pub stages: Vec<CompiledStage>,
+    /// Profile of the context the program was built for
+    pub profile: GlProfile,
+    /// Active vertex attributes of the linked program, keyed by name
+    pub attributes: std::collections::HashMap<String, AttributeInfo>,
+    /// Active uniforms of the linked program, keyed by name
+    pub uniforms: std::collections::HashMap<String, UniformInfo>,
+    /// Sampler uniforms and the texture units they were assigned, keyed by name
+    pub samplers: std::collections::HashMap<String, SamplerInfo>,
+    /// Active uniform blocks of the linked program, keyed by block name
+    pub uniform_blocks: std::collections::HashMap<String, UniformBlockInfo>,
     /// Store a Rc to the Gl instance to ensure that we can destroy this resource when dropped
     gl: std::rc::Rc<Gl>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(dead_code)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+    Geometry,
+    TessControl,
+    TessEvaluation,
+    Compute,
+}
+
+impl ShaderStage {
+    pub fn gl_type(&self) -> types::GLenum {
+        match self {
+            ShaderStage::Vertex => VERTEX_SHADER,
+            ShaderStage::Fragment => FRAGMENT_SHADER,
+            ShaderStage::Geometry => GEOMETRY_SHADER,
+            ShaderStage::TessControl => TESS_CONTROL_SHADER,
+            ShaderStage::TessEvaluation => TESS_EVALUATION_SHADER,
+            ShaderStage::Compute => COMPUTE_SHADER,
+        }
+    }
+    /// ES 3.0 and WebGL 2 only have vertex and fragment shaders
+    pub fn is_supported(&self, profile: GlProfile) -> bool {
+        match profile {
+            GlProfile::Core43 => true,
+            GlProfile::ES3 => matches!(self, ShaderStage::Vertex | ShaderStage::Fragment),
+        }
+    }
+}
+
+impl std::fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ShaderStage::Vertex => "Vertex",
+            ShaderStage::Fragment => "Fragment",
+            ShaderStage::Geometry => "Geometry",
+            ShaderStage::TessControl => "Tessellation control",
+            ShaderStage::TessEvaluation => "Tessellation evaluation",
+            ShaderStage::Compute => "Compute",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A single compiled stage of a shader program
+#[allow(dead_code)]
+pub struct CompiledStage {
+    pub stage: ShaderStage,
+    pub gl_handle: types::GLuint,
+    /// The source as handed to the driver, after translation to the profile's dialect
+    pub source: String,
+}
+
+#[derive(Debug, Clone)]
 pub struct ShaderAttributeBinding {
     pub name: String,
     pub index: u32,
 }
 
+/// Describes an active vertex attribute of a linked shader program
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct AttributeInfo {
+    pub name: String,
+    /// GL type of the attribute, i.e. FLOAT_VEC4
+    pub data_type: types::GLenum,
+    pub size: i32,
+    pub location: types::GLint,
+}
+
 #[derive(Debug)]
 pub struct ShaderCompilationError {
+    /// The log exactly as the driver reported it
     pub log: String,
+    /// The log parsed into individual messages, see `parse_shader_log`
+    pub diagnostics: Vec<ShaderDiagnostic>,
 }
 
 #[derive(Debug)]
+#[allow(dead_code)]
 pub enum ShaderError {
     ///null terminator in string for example
-    VertexSourceMalformed,
-    FragmentSourceMalformed,
+    SourceMalformed(ShaderStage),
     AttributeStringMalformed,
-    VertexShaderCompilationFailed(ShaderCompilationError),
-    FragmentShaderCompilationFailed(ShaderCompilationError),
+    SourceFileUnreadable(std::path::PathBuf, std::io::Error),
+    MalformedInclude(String),
+    IncludeCycle(std::path::PathBuf),
+    /// The stage does not exist on the profile of the context
+    UnsupportedStage(ShaderStage, GlProfile),
+    /// Stages were missing or given twice, or compute was combined with other stages
+    InvalidStageCombination,
+    CompilationFailed(ShaderStage, ShaderCompilationError),
+    ProgramLinkFailed(ShaderCompilationError),
+    /// A binding was given for an attribute that the linked program does not use
+    AttributeNotActive(String),
+    /// The attribute ended up at another location than the binding asked for, which happens if
+    /// the source declares an explicit location for it
+    AttributeLocationMismatch {
+        name: String,
+        index: u32,
+        location: i32,
+    },
+    UniformNotFound(String),
+    UniformBlockNotFound(String),
+    SamplerNotFound(String),
+    /// Tried to bind past the end of a sampler array
+    SamplerIndexOutOfRange {
+        name: String,
+        index: u32,
+        size: i32,
+    },
+    StorageBlockNotFound(String),
+    StorageBlocksUnsupported(GlProfile),
+    UniformTypeMismatch {
+        name: String,
+        data_type: types::GLenum,
+    },
+    UniformArrayTooLarge {
+        name: String,
+        size: i32,
+        count: i32,
+    },
+    /// Tried to dispatch a program that has no compute stage
+    NotComputeShader,
 }
 
 impl std::error::Error for ShaderError {}
 impl std::fmt::Display for ShaderError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let to_write = match self {
-            ShaderError::VertexSourceMalformed => {
-                String::from("Vertex input string is malformed. Contains zero bytes maybe")
-            }
-            ShaderError::FragmentSourceMalformed => {
-                String::from("Fragment input string is malformed. Contains zero bytes maybe")
-            }
+            ShaderError::SourceMalformed(stage) => std::format!(
+                "{} input string is malformed. Contains zero bytes maybe",
+                stage
+            ),
             ShaderError::AttributeStringMalformed => {
                 String::from("Attribute name input string is malformed. Contains zero bytes maybe")
             }
-            ShaderError::VertexShaderCompilationFailed(l) => {
-                std::format!("Vertex shader failed to compile: {}", l.log)
+            ShaderError::SourceFileUnreadable(path, e) => {
+                std::format!("Could not read shader source '{}': {}", path.display(), e)
+            }
+            ShaderError::MalformedInclude(line) => {
+                std::format!("Malformed include directive: {}", line)
+            }
+            ShaderError::IncludeCycle(path) => {
+                std::format!("'{}' ends up including itself", path.display())
+            }
+            ShaderError::UnsupportedStage(stage, profile) => std::format!(
+                "{} shaders are not supported on the {:?} profile",
+                stage,
+                profile
+            ),
+            ShaderError::InvalidStageCombination => String::from(
+                "Shader stages must be unique, and a program is either a single compute stage \
+                 or has both a vertex and a fragment stage",
+            ),
+            ShaderError::CompilationFailed(stage, l) => {
+                std::format!("{} shader failed to compile: {}", stage, l)
             }
-            ShaderError::FragmentShaderCompilationFailed(l) => {
-                std::format!("Fragment shader failed to compile: {}", l.log)
+            ShaderError::ProgramLinkFailed(l) => {
+                std::format!("Shader program failed to link: {}", l)
+            }
+            ShaderError::AttributeNotActive(name) => std::format!(
+                "Attribute '{}' was given a binding but is not an active attribute of the shader",
+                name
+            ),
+            ShaderError::AttributeLocationMismatch {
+                name,
+                index,
+                location,
+            } => std::format!(
+                "Attribute '{}' was bound to index {} but ended up at location {}",
+                name,
+                index,
+                location
+            ),
+            ShaderError::UniformNotFound(name) => {
+                std::format!("No active uniform named '{}' in shader", name)
+            }
+            ShaderError::UniformBlockNotFound(name) => {
+                std::format!("No active uniform block named '{}' in shader", name)
+            }
+            ShaderError::SamplerNotFound(name) => {
+                std::format!("No active sampler uniform named '{}' in shader", name)
+            }
+            ShaderError::SamplerIndexOutOfRange { name, index, size } => std::format!(
+                "Tried to bind element {} of sampler array '{}' which only has {}",
+                index,
+                name,
+                size
+            ),
+            ShaderError::StorageBlockNotFound(name) => {
+                std::format!("No active shader storage block named '{}' in shader", name)
+            }
+            ShaderError::StorageBlocksUnsupported(profile) => std::format!(
+                "Shader storage blocks are not supported on the {:?} profile",
+                profile
+            ),
+            ShaderError::UniformTypeMismatch { name, data_type } => std::format!(
+                "Value is not compatible with uniform '{}' of GL type 0x{:X}",
+                name,
+                data_type
+            ),
+            ShaderError::UniformArrayTooLarge { name, size, count } => std::format!(
+                "Tried to set {} elements of uniform array '{}' which only has {}",
+                count,
+                name,
+                size
+            ),
+            ShaderError::NotComputeShader => {
+                String::from("Only shaders with a compute stage can be dispatched")
             }
         };
         write!(f, "{}", to_write)
@@ -56,121 +238,250 @@ impl std::fmt::Display for ShaderError {
 }
 
 impl Shader {
+    #[allow(dead_code)]
     pub fn new(
         vertex_source: &str,
         fragment_source: &str,
         attribute_bindings: &[ShaderAttributeBinding],
         gl: std::rc::Rc<Gl>,
     ) -> Result<Self, ShaderError> {
-        //compile vertex shader
-        let vertex_gl_handle = unsafe { gl.CreateShader(VERTEX_SHADER) };
-        let source = std::ffi::CString::new(vertex_source)
-            .map_err(|_| ShaderError::VertexSourceMalformed)?;
-        unsafe { gl.ShaderSource(vertex_gl_handle, 1, &source.as_ptr(), std::ptr::null()) };
-        unsafe { gl.CompileShader(vertex_gl_handle) };
-
-        let mut success: types::GLint = 0;
-        unsafe { gl.GetShaderiv(vertex_gl_handle, COMPILE_STATUS, &mut success as *mut i32) };
-
-        if success as u8 == FALSE {
-            let mut log_size: types::GLint = 0;
-            unsafe { gl.GetShaderiv(vertex_gl_handle, INFO_LOG_LENGTH, &mut log_size as *mut i32) };
-
-            let mut error_log: Vec<types::GLchar> = Vec::with_capacity(log_size as usize);
-            error_log.resize_with(log_size as usize, Default::default);
-
-            unsafe {
-                gl.GetShaderInfoLog(
-                    vertex_gl_handle,
-                    log_size,
-                    &mut log_size as *mut i32,
-                    error_log.as_ptr() as *mut _,
-                )
-            };
+        Self::from_stages(
+            &[
+                (ShaderStage::Vertex, vertex_source),
+                (ShaderStage::Fragment, fragment_source),
+            ],
+            attribute_bindings,
+            gl,
+        )
+    }
+    /// Like `new`, but loads the linked program from the binary cache when possible and stores
+    /// it there after compiling otherwise. `defines` are the ones the sources were preprocessed
+    /// with
+    pub fn new_cached(
+        vertex_source: &str,
+        fragment_source: &str,
+        attribute_bindings: &[ShaderAttributeBinding],
+        defines: &ShaderDefines,
+        cache: &ProgramBinaryCache,
+        gl: std::rc::Rc<Gl>,
+    ) -> Result<Self, ShaderError> {
+        Self::from_stages_cached(
+            &[
+                (ShaderStage::Vertex, vertex_source),
+                (ShaderStage::Fragment, fragment_source),
+            ],
+            attribute_bindings,
+            defines,
+            cache,
+            gl,
+        )
+    }
+    /// Builds a compute program. Only available on the Core43 profile
+    #[allow(dead_code)]
+    pub fn new_compute(compute_source: &str, gl: std::rc::Rc<Gl>) -> Result<Self, ShaderError> {
+        Self::from_stages(&[(ShaderStage::Compute, compute_source)], &[], gl)
+    }
+    /// Builds a program from any set of stages. Graphics programs need at least a vertex and a
+    /// fragment stage, while compute programs consist of only the compute stage
+    pub fn from_stages(
+        stages: &[(ShaderStage, &str)],
+        attribute_bindings: &[ShaderAttributeBinding],
+        gl: std::rc::Rc<Gl>,
+    ) -> Result<Self, ShaderError> {
+        Self::create(stages, attribute_bindings, None, gl)
+    }
+    pub fn from_stages_cached(
+        stages: &[(ShaderStage, &str)],
+        attribute_bindings: &[ShaderAttributeBinding],
+        defines: &ShaderDefines,
+        cache: &ProgramBinaryCache,
+        gl: std::rc::Rc<Gl>,
+    ) -> Result<Self, ShaderError> {
+        Self::create(stages, attribute_bindings, Some((cache, defines)), gl)
+    }
+    fn create(
+        stages: &[(ShaderStage, &str)],
+        attribute_bindings: &[ShaderAttributeBinding],
+        cache: Option<(&ProgramBinaryCache, &ShaderDefines)>,
+        gl: std::rc::Rc<Gl>,
+    ) -> Result<Self, ShaderError> {
+        let profile = GlProfile::detect(&gl);
+        validate_stages(stages, profile)?;
+
+        //sources are written once and adapted to whatever profile the context ended up with
+        let sources: Vec<(ShaderStage, String)> = stages
+            .iter()
+            .map(|(stage, source)| (*stage, translate_glsl(source, *stage, profile)))
+            .collect();
+
+        //try the binary cache first. It is keyed on the translated sources since those are what
+        //the driver actually compiles. Contexts without program binaries, such as WebGL, skip
+        //the cache entirely since they do not know the retrievable hint either
+        let cache = cache.filter(|_| ProgramBinaryCache::is_supported(&gl));
+        let cache = cache.map(|(cache, defines)| {
+            let source_strs: Vec<&str> = sources.iter().map(|(_, source)| &source[..]).collect();
+            let key = cache.key(&source_strs, defines, attribute_bindings, &gl);
+            (cache, key)
+        });
+
+        if let Some((cache, key)) = cache {
+            if let Some(program_gl_handle) = cache.load(key, &gl) {
+                let stages = sources
+                    .iter()
+                    .map(|(stage, source)| CompiledStage {
+                        stage: *stage,
+                        gl_handle: 0,
+                        source: source.clone(),
+                    })
+                    .collect();
+
+                let cached = Self::from_linked_program(
+                    program_gl_handle,
+                    stages,
+                    profile,
+                    attribute_bindings,
+                    gl.clone(),
+                );
+
+                //a cached program that does not match the bindings is simply rebuilt
+                if let Ok(shader) = cached {
+                    return Ok(shader);
+                }
+            }
+        }
 
-            let error_log = error_log
-                .into_iter()
-                .map(|c| c as u8 as char)
-                .collect::<String>();
+        //compile all stages
+        let mut compiled_stages: Vec<CompiledStage> = Vec::with_capacity(sources.len());
+        for (stage, source) in sources {
+            match compile_stage(stage, &source, &gl) {
+                Ok(gl_handle) => compiled_stages.push(CompiledStage {
+                    stage,
+                    gl_handle,
+                    source,
+                }),
+                Err(e) => {
+                    for compiled in &compiled_stages {
+                        unsafe { gl.DeleteShader(compiled.gl_handle) };
+                    }
+                    return Err(e);
+                }
+            }
+        }
 
-            unsafe {
-                gl.DeleteShader(vertex_gl_handle);
+        //assemble shader program. Attribute locations must be bound before linking to have effect
+        let program_gl_handle = unsafe { gl.CreateProgram() };
+        let delete_all = || unsafe {
+            gl.DeleteProgram(program_gl_handle);
+            for compiled in &compiled_stages {
+                gl.DeleteShader(compiled.gl_handle);
             }
+        };
 
-            return Err(ShaderError::VertexShaderCompilationFailed(
-                ShaderCompilationError { log: error_log },
-            ));
+        for binding in attribute_bindings {
+            let name = match std::ffi::CString::new(&binding.name[..]) {
+                Ok(name) => name,
+                Err(_) => {
+                    delete_all();
+                    return Err(ShaderError::AttributeStringMalformed);
+                }
+            };
+            unsafe { gl.BindAttribLocation(program_gl_handle, binding.index, name.as_ptr()) }
         }
 
-        //compile fragment shader
-        let fragment_gl_handle = unsafe { gl.CreateShader(FRAGMENT_SHADER) };
-        let source = std::ffi::CString::new(fragment_source)
-            .map_err(|_| ShaderError::FragmentSourceMalformed)?;
-        unsafe { gl.ShaderSource(fragment_gl_handle, 1, &source.as_ptr(), std::ptr::null()) };
-        unsafe { gl.CompileShader(fragment_gl_handle) };
+        unsafe {
+            if cache.is_some() {
+                gl.ProgramParameteri(
+                    program_gl_handle,
+                    PROGRAM_BINARY_RETRIEVABLE_HINT,
+                    TRUE as i32,
+                );
+            }
+            for compiled in &compiled_stages {
+                gl.AttachShader(program_gl_handle, compiled.gl_handle);
+            }
+            gl.LinkProgram(program_gl_handle);
+        }
 
         let mut success: types::GLint = 0;
-        unsafe { gl.GetShaderiv(fragment_gl_handle, COMPILE_STATUS, &mut success as *mut i32) };
+        unsafe { gl.GetProgramiv(program_gl_handle, LINK_STATUS, &mut success as *mut i32) };
 
         if success as u8 == FALSE {
-            let mut log_size: types::GLint = 0;
-            unsafe {
-                gl.GetShaderiv(
-                    fragment_gl_handle,
-                    INFO_LOG_LENGTH,
-                    &mut log_size as *mut i32,
-                )
-            };
-
-            let mut error_log: Vec<types::GLchar> = Vec::with_capacity(log_size as usize);
-            error_log.resize_with(log_size as usize, Default::default);
-
-            unsafe {
-                gl.GetShaderInfoLog(
-                    fragment_gl_handle,
-                    log_size,
-                    &mut log_size as *mut i32,
-                    error_log.as_ptr() as *mut _,
-                )
-            };
+            let error_log = program_info_log(program_gl_handle, &gl);
+            delete_all();
 
-            let error_log = error_log
-                .into_iter()
-                .map(|c| c as u8 as char)
-                .collect::<String>();
+            return Err(ShaderError::ProgramLinkFailed(ShaderCompilationError::new(
+                error_log, None, None,
+            )));
+        }
 
-            unsafe {
-                gl.DeleteShader(vertex_gl_handle);
-                gl.DeleteShader(fragment_gl_handle);
-            }
+        let res = Self::from_linked_program(
+            program_gl_handle,
+            compiled_stages,
+            profile,
+            attribute_bindings,
+            gl,
+        )?;
 
-            return Err(ShaderError::FragmentShaderCompilationFailed(
-                ShaderCompilationError { log: error_log },
-            ));
+        // Only cache programs that passed validation, a rejected one would be loaded next run
+        if let Some((cache, key)) = cache {
+            cache.store(key, res.program_gl_handle, &res.gl);
         }
 
-        //assemble shader program
-        let program_gl_handle = unsafe { gl.CreateProgram() };
-        unsafe {
-            gl.AttachShader(program_gl_handle, vertex_gl_handle);
-            gl.AttachShader(program_gl_handle, fragment_gl_handle);
-            gl.LinkProgram(program_gl_handle);
-        }
+        Ok(res)
+    }
+    /// Finishes a successfully linked program by reflecting it and checking the attribute
+    /// bindings. Takes ownership of the GL handles, so they are deleted if this fails
+    fn from_linked_program(
+        program_gl_handle: types::GLuint,
+        stages: Vec<CompiledStage>,
+        profile: GlProfile,
+        attribute_bindings: &[ShaderAttributeBinding],
+        gl: std::rc::Rc<Gl>,
+    ) -> Result<Self, ShaderError> {
+        let attributes = query_active_attributes(program_gl_handle, &gl);
+        let uniforms = query_active_uniforms(program_gl_handle, &gl);
+        let uniform_blocks = query_active_uniform_blocks(program_gl_handle, &gl);
+        let samplers = assign_sampler_units(program_gl_handle, &uniforms, &gl);
 
+        let res = Self {
+            program_gl_handle,
+            stages,
+            profile,
+            attributes,
+            uniforms,
+            samplers,
+            uniform_blocks,
+            gl,
+        };
+
+        //make sure that every binding refers to an attribute that actually ended up in the program
         for binding in attribute_bindings {
-            let name = std::ffi::CString::new(&binding.name[..])
-                .map_err(|_| ShaderError::AttributeStringMalformed)?;
-            unsafe { gl.BindAttribLocation(program_gl_handle, binding.index, name.as_ptr()) }
+            match res.attributes.get(&binding.name) {
+                None => return Err(ShaderError::AttributeNotActive(binding.name.clone())),
+                Some(attribute) if attribute.location != binding.index as i32 => {
+                    return Err(ShaderError::AttributeLocationMismatch {
+                        name: binding.name.clone(),
+                        index: binding.index,
+                        location: attribute.location,
+                    })
+                }
+                Some(_) => {}
+            }
         }
 
-        Ok(Self {
-            program_gl_handle,
-            vertex_gl_handle,
-            fragment_gl_handle,
-            vertex_source: String::from(vertex_source),
-            fragment_source: String::from(fragment_source),
-            gl,
-        })
+        Ok(res)
+    }
+    #[allow(dead_code)]
+    pub fn from_files(
+        vertex_path: &std::path::Path,
+        fragment_path: &std::path::Path,
+        attribute_bindings: &[ShaderAttributeBinding],
+        gl: std::rc::Rc<Gl>,
+    ) -> Result<Self, ShaderError> {
+        let vertex_source = read_source_file(vertex_path)?;
+        let fragment_source = read_source_file(fragment_path)?;
+
+        Self::new(&vertex_source, &fragment_source, attribute_bindings, gl)
     }
     pub fn bind(&self, gl: &Gl) {
         unsafe {
@@ -182,65 +493,380 @@ impl Shader {
             gl.UseProgram(0);
         }
     }
+    /// Returns the translated source of a stage, if the program has it
+    #[allow(dead_code)]
+    pub fn source(&self, stage: ShaderStage) -> Option<&str> {
+        self.stages
+            .iter()
+            .find(|compiled| compiled.stage == stage)
+            .map(|compiled| &compiled.source[..])
+    }
+    #[allow(dead_code)]
+    pub fn is_compute(&self) -> bool {
+        self.source(ShaderStage::Compute).is_some()
+    }
+    /// Runs the compute stage with the given amount of work groups in each dimension. Binds the
+    /// shader. Use `memory_barrier` before reading back anything the shader wrote
+    #[allow(dead_code)]
+    pub fn dispatch_compute(&self, work_groups: glm::UVec3, gl: &Gl) -> Result<(), ShaderError> {
+        if !self.is_compute() {
+            return Err(ShaderError::NotComputeShader);
+        }
+
+        self.bind(gl);
+        unsafe {
+            gl.DispatchCompute(work_groups.x, work_groups.y, work_groups.z);
+        }
+        Ok(())
+    }
+    #[allow(dead_code)]
+    pub fn uniform(&self, name: &str) -> Option<&UniformInfo> {
+        self.uniforms.get(name)
+    }
+    /// Binds a texture to the unit of the named sampler uniform. `target` of the sampler has to
+    /// match the texture. Does not require the shader to be bound
+    #[allow(dead_code)]
+    pub fn bind_texture(
+        &self,
+        name: &str,
+        texture_gl_handle: types::GLuint,
+        gl: &Gl,
+    ) -> Result<(), ShaderError> {
+        self.bind_texture_element(name, 0, texture_gl_handle, gl)
+    }
+    /// Like `bind_texture` but for a single element of a sampler array
+    #[allow(dead_code)]
+    pub fn bind_texture_element(
+        &self,
+        name: &str,
+        index: u32,
+        texture_gl_handle: types::GLuint,
+        gl: &Gl,
+    ) -> Result<(), ShaderError> {
+        let sampler = self
+            .samplers
+            .get(name)
+            .ok_or_else(|| ShaderError::SamplerNotFound(String::from(name)))?;
+
+        if index as i32 >= sampler.size {
+            return Err(ShaderError::SamplerIndexOutOfRange {
+                name: String::from(name),
+                index,
+                size: sampler.size,
+            });
+        }
+
+        unsafe {
+            gl.ActiveTexture(TEXTURE0 + sampler.unit + index);
+            gl.BindTexture(sampler.target, texture_gl_handle);
+        }
+        Ok(())
+    }
+    /// Makes the named uniform block read its data from the buffer bound to the given binding
+    /// point. Does not require the shader to be bound
+    pub fn bind_uniform_block(
+        &self,
+        name: &str,
+        binding_point: u32,
+        gl: &Gl,
+    ) -> Result<(), ShaderError> {
+        let block = self
+            .uniform_blocks
+            .get(name)
+            .ok_or_else(|| ShaderError::UniformBlockNotFound(String::from(name)))?;
+
+        unsafe { gl.UniformBlockBinding(self.program_gl_handle, block.index, binding_point) };
+        Ok(())
+    }
+    /// Makes the named shader storage block access the buffer bound to the given binding point of
+    /// SHADER_STORAGE_BUFFER. Storage blocks only exist on the Core43 profile
+    #[allow(dead_code)]
+    pub fn bind_storage_block(
+        &self,
+        name: &str,
+        binding_point: u32,
+        gl: &Gl,
+    ) -> Result<(), ShaderError> {
+        if self.profile != GlProfile::Core43 {
+            return Err(ShaderError::StorageBlocksUnsupported(self.profile));
+        }
+
+        let c_name = std::ffi::CString::new(name)
+            .map_err(|_| ShaderError::StorageBlockNotFound(String::from(name)))?;
+        let index = unsafe {
+            gl.GetProgramResourceIndex(
+                self.program_gl_handle,
+                SHADER_STORAGE_BLOCK,
+                c_name.as_ptr(),
+            )
+        };
+
+        if index == INVALID_INDEX {
+            return Err(ShaderError::StorageBlockNotFound(String::from(name)));
+        }
+
+        unsafe { gl.ShaderStorageBlockBinding(self.program_gl_handle, index, binding_point) };
+        Ok(())
+    }
+    /// Sets the value of a uniform of this shader. The shader must be bound. Sampler uniforms
+    /// are managed by the shader itself, see `bind_texture`
+    pub fn set_uniform<T: UniformValue + ?Sized>(
+        &self,
+        name: &str,
+        value: &T,
+        gl: &Gl,
+    ) -> Result<(), ShaderError> {
+        let uniform = self
+            .uniforms
+            .get(name)
+            .ok_or_else(|| ShaderError::UniformNotFound(String::from(name)))?;
+
+        if !T::is_compatible(uniform.data_type) {
+            return Err(ShaderError::UniformTypeMismatch {
+                name: String::from(name),
+                data_type: uniform.data_type,
+            });
+        }
+
+        if value.count() > uniform.size {
+            return Err(ShaderError::UniformArrayTooLarge {
+                name: String::from(name),
+                size: uniform.size,
+                count: value.count(),
+            });
+        }
+
+        value.upload(uniform.location, gl);
+        Ok(())
+    }
 }
 
 impl Drop for Shader {
     fn drop(&mut self) {
         unsafe {
             self.gl.DeleteProgram(self.program_gl_handle);
-            self.gl.DeleteShader(self.vertex_gl_handle);
-            self.gl.DeleteShader(self.fragment_gl_handle);
+            for compiled in &self.stages {
+                self.gl.DeleteShader(compiled.gl_handle);
+            }
         }
     }
 }
 
-pub fn new_default_shader(gl: std::rc::Rc<crate::gl::Gl>) -> crate::gl::Shader {
-    crate::gl::Shader::new(
-        VERTEX_SOURCE,
-        FRAGMENT_SOURCE,
-        &[
-            ShaderAttributeBinding {
-                name: String::from("position"),
-                index: 0,
-            },
-            ShaderAttributeBinding {
-                name: String::from("color"),
-                index: 1,
-            },
-        ],
-        gl,
-    )
-    .unwrap() //unwrap since the inputs are hard coded
+fn validate_stages(stages: &[(ShaderStage, &str)], profile: GlProfile) -> Result<(), ShaderError> {
+    if let Some((stage, _)) = stages
+        .iter()
+        .find(|(stage, _)| !stage.is_supported(profile))
+    {
+        return Err(ShaderError::UnsupportedStage(*stage, profile));
+    }
+
+    let has = |wanted: ShaderStage| stages.iter().any(|(stage, _)| *stage == wanted);
+    let unique = stages
+        .iter()
+        .enumerate()
+        .all(|(i, (stage, _))| stages[..i].iter().all(|(other, _)| other != stage));
+
+    let valid = if has(ShaderStage::Compute) {
+        stages.len() == 1
+    } else {
+        has(ShaderStage::Vertex) && has(ShaderStage::Fragment)
+    };
+
+    if unique && valid {
+        Ok(())
+    } else {
+        Err(ShaderError::InvalidStageCombination)
+    }
+}
+
+fn compile_stage(stage: ShaderStage, source: &str, gl: &Gl) -> Result<types::GLuint, ShaderError> {
+    let c_source =
+        std::ffi::CString::new(source).map_err(|_| ShaderError::SourceMalformed(stage))?;
+
+    let gl_handle = unsafe { gl.CreateShader(stage.gl_type()) };
+    unsafe { gl.ShaderSource(gl_handle, 1, &c_source.as_ptr(), std::ptr::null()) };
+    unsafe { gl.CompileShader(gl_handle) };
+
+    let mut success: types::GLint = 0;
+    unsafe { gl.GetShaderiv(gl_handle, COMPILE_STATUS, &mut success as *mut i32) };
+
+    if success as u8 == FALSE {
+        let mut log_size: types::GLint = 0;
+        unsafe { gl.GetShaderiv(gl_handle, INFO_LOG_LENGTH, &mut log_size as *mut i32) };
+
+        let mut error_log: Vec<types::GLchar> = Vec::with_capacity(log_size as usize);
+        error_log.resize_with(log_size as usize, Default::default);
+
+        unsafe {
+            gl.GetShaderInfoLog(
+                gl_handle,
+                log_size,
+                &mut log_size as *mut i32,
+                error_log.as_ptr() as *mut _,
+            )
+        };
+
+        let error_log = error_log
+            .into_iter()
+            .take(log_size as usize)
+            .map(|c| c as u8 as char)
+            .collect::<String>();
+
+        unsafe {
+            gl.DeleteShader(gl_handle);
+        }
+
+        return Err(ShaderError::CompilationFailed(
+            stage,
+            ShaderCompilationError::new(error_log, Some(stage), Some(source)),
+        ));
+    }
+
+    Ok(gl_handle)
+}
+
+pub fn read_source_file(path: &std::path::Path) -> Result<String, ShaderError> {
+    std::fs::read_to_string(path)
+        .map_err(|e| ShaderError::SourceFileUnreadable(path.to_path_buf(), e))
+}
+
+fn program_info_log(program_gl_handle: types::GLuint, gl: &Gl) -> String {
+    let mut log_size: types::GLint = 0;
+    unsafe {
+        gl.GetProgramiv(
+            program_gl_handle,
+            INFO_LOG_LENGTH,
+            &mut log_size as *mut i32,
+        )
+    };
+
+    let mut error_log: Vec<types::GLchar> = Vec::with_capacity(log_size as usize);
+    error_log.resize_with(log_size as usize, Default::default);
+
+    unsafe {
+        gl.GetProgramInfoLog(
+            program_gl_handle,
+            log_size,
+            &mut log_size as *mut i32,
+            error_log.as_ptr() as *mut _,
+        )
+    };
+
+    error_log
+        .into_iter()
+        .take(log_size as usize)
+        .map(|c| c as u8 as char)
+        .collect::<String>()
 }
 
-const VERTEX_SOURCE: &str = "#version 300 es
+fn query_active_attributes(
+    program_gl_handle: types::GLuint,
+    gl: &Gl,
+) -> std::collections::HashMap<String, AttributeInfo> {
+    let mut attributes = std::collections::HashMap::new();
+
+    let mut count: types::GLint = 0;
+    let mut max_name_length: types::GLint = 0;
+    unsafe {
+        gl.GetProgramiv(program_gl_handle, ACTIVE_ATTRIBUTES, &mut count as *mut i32);
+        gl.GetProgramiv(
+            program_gl_handle,
+            ACTIVE_ATTRIBUTE_MAX_LENGTH,
+            &mut max_name_length as *mut i32,
+        );
+    }
+
+    for index in 0..count as u32 {
+        let mut name_buffer: Vec<u8> = vec![0; max_name_length as usize];
+        let mut name_length: types::GLsizei = 0;
+        let mut size: types::GLint = 0;
+        let mut data_type: types::GLenum = 0;
+
+        unsafe {
+            gl.GetActiveAttrib(
+                program_gl_handle,
+                index,
+                max_name_length,
+                &mut name_length as *mut i32,
+                &mut size as *mut i32,
+                &mut data_type as *mut u32,
+                name_buffer.as_mut_ptr() as *mut types::GLchar,
+            )
+        };
+        name_buffer.truncate(name_length as usize);
+
+        let c_name = match std::ffi::CString::new(name_buffer) {
+            Ok(c_name) => c_name,
+            Err(_) => continue,
+        };
+        let location = unsafe { gl.GetAttribLocation(program_gl_handle, c_name.as_ptr()) };
 
-layout(location=0) in vec4 position; //will be compatible with vec2 and vec3 attributes. extended to vec4: (0, 0, 0, 1) for missing components
-layout(location=1) in vec4 color;
+        //built-ins such as gl_VertexID are reported as active but have no location
+        if location < 0 {
+            continue;
+        }
 
-uniform mat4 view_projection;
+        let name = c_name.to_string_lossy().into_owned();
+        attributes.insert(
+            name.clone(),
+            AttributeInfo {
+                name,
+                data_type,
+                size,
+                location,
+            },
+        );
+    }
 
-out vec4 v_color;
+    attributes
+}
 
-void main()
-{
-    gl_Position = view_projection * position;
-    v_color = color;
+pub fn default_attribute_bindings() -> Vec<ShaderAttributeBinding> {
+    vec![
+        ShaderAttributeBinding {
+            name: String::from("position"),
+            index: 0,
+        },
+        ShaderAttributeBinding {
+            name: String::from("color"),
+            index: 1,
+        },
+    ]
 }
-";
 
-const FRAGMENT_SOURCE: &str = "#version 300 es
+pub fn new_default_shader(gl: std::rc::Rc<crate::gl::Gl>) -> crate::gl::Shader {
+    crate::gl::Shader::new_cached(
+        VERTEX_SOURCE,
+        FRAGMENT_SOURCE,
+        &default_attribute_bindings(),
+        &ShaderDefines::new(),
+        &ProgramBinaryCache::new(DEFAULT_PROGRAM_CACHE_DIR),
+        gl,
+    )
+    .unwrap() //unwrap since the inputs are hard coded
+}
 
-precision mediump float;
+const VERTEX_SOURCE: &str = include_str!("../../shaders/default.vert");
+const FRAGMENT_SOURCE: &str = include_str!("../../shaders/default.frag");
 
-in vec4 v_color;
+/// Paths of the default shader sources, used to hot-reload them during development
+pub const DEFAULT_VERTEX_SHADER_PATH: &str = "shaders/default.vert";
+pub const DEFAULT_FRAGMENT_SHADER_PATH: &str = "shaders/default.frag";
 
-out vec4 out_color;
+/// Directory that linked program binaries are cached in between runs
+pub const DEFAULT_PROGRAM_CACHE_DIR: &str = "shader_cache";
 
-void main()
-{
-    out_color = v_color;
+/// Builds the default shader from its files on disk rather than from the baked in sources
+pub fn default_shader_builder() -> ShaderBuilder {
+    ShaderBuilder::from_files(DEFAULT_VERTEX_SHADER_PATH, DEFAULT_FRAGMENT_SHADER_PATH)
+        .attribute_bindings(default_attribute_bindings())
 }
-";
 
-pub const DEFAULT_PROJECTION_UNIFORM: &str = "view_projection";
+/// Uniform block holding the camera matrices, see `CameraUniforms`
+pub const DEFAULT_CAMERA_BLOCK: &str = "Camera";
+/// Binding point that the camera uniform buffer is bound to
+pub const DEFAULT_CAMERA_BINDING_POINT: u32 = 0;
+/// Transform from model space to world space
+pub const DEFAULT_MODEL_UNIFORM: &str = "model";
+/// Color that the vertex colors are multiplied with
+pub const DEFAULT_TINT_UNIFORM: &str = "tint";
@@ -0,0 +1,195 @@
+use super::*;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Where the source of a shader stage comes from
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub enum ShaderSource {
+    Inline(String),
+    File(PathBuf),
+}
+
+impl ShaderSource {
+    fn load(&self) -> Result<(String, String), ShaderError> {
+        match self {
+            ShaderSource::Inline(source) => Ok((String::from("<inline>"), source.clone())),
+            ShaderSource::File(path) => Ok((path.display().to_string(), read_source_file(path)?)),
+        }
+    }
+}
+
+/// Assembles a shader from sources that may contain `#include` directives, with a set of
+/// `#define`s injected. Used where `Shader::new` with plain strings is not enough
+#[derive(Debug, Clone)]
+pub struct ShaderBuilder {
+    stages: Vec<(ShaderStage, ShaderSource)>,
+    include_dir: PathBuf,
+    defines: ShaderDefines,
+    attribute_bindings: Vec<ShaderAttributeBinding>,
+    binary_cache: Option<ProgramBinaryCache>,
+}
+
+impl ShaderBuilder {
+    pub fn new(vertex: ShaderSource, fragment: ShaderSource) -> Self {
+        Self::empty()
+            .stage(ShaderStage::Vertex, vertex)
+            .stage(ShaderStage::Fragment, fragment)
+    }
+    #[allow(dead_code)]
+    pub fn compute(compute: ShaderSource) -> Self {
+        Self::empty().stage(ShaderStage::Compute, compute)
+    }
+    fn empty() -> Self {
+        Self {
+            stages: Vec::new(),
+            include_dir: PathBuf::from("shaders"),
+            defines: ShaderDefines::new(),
+            attribute_bindings: Vec::new(),
+            binary_cache: None,
+        }
+    }
+    pub fn from_files(vertex_path: impl Into<PathBuf>, fragment_path: impl Into<PathBuf>) -> Self {
+        Self::new(
+            ShaderSource::File(vertex_path.into()),
+            ShaderSource::File(fragment_path.into()),
+        )
+    }
+    /// Adds a stage to the program, replacing the source if the stage was already added
+    pub fn stage(mut self, stage: ShaderStage, source: ShaderSource) -> Self {
+        self.stages.retain(|(existing, _)| *existing != stage);
+        self.stages.push((stage, source));
+        self
+    }
+    /// Directory that `#include` directives are resolved against. Defaults to "shaders"
+    #[allow(dead_code)]
+    pub fn include_dir(mut self, include_dir: impl Into<PathBuf>) -> Self {
+        self.include_dir = include_dir.into();
+        self
+    }
+    pub fn define(mut self, name: &str, value: &str) -> Self {
+        self.defines.insert(name, value);
+        self
+    }
+    #[allow(dead_code)]
+    pub fn defines(mut self, defines: ShaderDefines) -> Self {
+        self.defines = defines;
+        self
+    }
+    pub fn attribute_bindings(mut self, attribute_bindings: Vec<ShaderAttributeBinding>) -> Self {
+        self.attribute_bindings = attribute_bindings;
+        self
+    }
+    /// Caches the linked program binary in the given directory to speed up later builds
+    #[allow(dead_code)]
+    pub fn binary_cache(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.binary_cache = Some(ProgramBinaryCache::new(dir));
+        self
+    }
+    pub fn build(&self, gl: std::rc::Rc<Gl>) -> Result<Shader, ShaderError> {
+        let preprocessed = self
+            .stages
+            .iter()
+            .map(|(stage, source)| Ok((*stage, self.preprocess(source)?)))
+            .collect::<Result<Vec<_>, ShaderError>>()?;
+
+        let stages: Vec<(ShaderStage, &str)> = preprocessed
+            .iter()
+            .map(|(stage, preprocessed)| (*stage, &preprocessed.source[..]))
+            .collect();
+
+        let result = match &self.binary_cache {
+            Some(cache) => Shader::from_stages_cached(
+                &stages,
+                &self.attribute_bindings,
+                &self.defines,
+                cache,
+                gl,
+            ),
+            None => Shader::from_stages(&stages, &self.attribute_bindings, gl),
+        };
+
+        //point diagnostics at the files they came from rather than at source string numbers
+        result.map_err(|e| match e {
+            ShaderError::CompilationFailed(stage, mut error) => {
+                if let Some((_, preprocessed)) = preprocessed.iter().find(|(s, _)| *s == stage) {
+                    error.set_source_names(&preprocessed.source_names);
+                }
+                ShaderError::CompilationFailed(stage, error)
+            }
+            e => e,
+        })
+    }
+    /// All files on disk that the shader is built from, including the ones pulled in through
+    /// `#include` directives
+    pub fn dependencies(&self) -> Result<Vec<PathBuf>, ShaderError> {
+        let mut dependencies = self.source_files();
+
+        for (_, source) in &self.stages {
+            for path in self.preprocess(source)?.dependencies {
+                if !dependencies.contains(&path) {
+                    dependencies.push(path);
+                }
+            }
+        }
+
+        Ok(dependencies)
+    }
+    /// The top level source files of the shader, not counting includes
+    pub fn source_files(&self) -> Vec<PathBuf> {
+        self.stages
+            .iter()
+            .filter_map(|(_, source)| match source {
+                ShaderSource::File(path) => Some(path.clone()),
+                ShaderSource::Inline(_) => None,
+            })
+            .collect()
+    }
+    fn preprocess(&self, source: &ShaderSource) -> Result<PreprocessedSource, ShaderError> {
+        let (name, text) = source.load()?;
+        preprocess(&text, &name, &self.include_dir, &self.defines)
+    }
+}
+
+/// Compiles variants of the same shader sources with different define sets on demand, and keeps
+/// them around so that every variant is only compiled once
+#[allow(dead_code)]
+pub struct ShaderVariants {
+    builder: ShaderBuilder,
+    variants: HashMap<ShaderDefines, Shader>,
+    gl: std::rc::Rc<Gl>,
+}
+
+#[allow(dead_code)]
+impl ShaderVariants {
+    /// Any defines already set on the builder are shared by all variants
+    pub fn new(builder: ShaderBuilder, gl: std::rc::Rc<Gl>) -> Self {
+        Self {
+            builder,
+            variants: HashMap::new(),
+            gl,
+        }
+    }
+    /// Returns the variant for the given define set, compiling it if it does not exist yet
+    pub fn get(&mut self, defines: &ShaderDefines) -> Result<&Shader, ShaderError> {
+        if !self.variants.contains_key(defines) {
+            let mut builder = self.builder.clone();
+            for (name, value) in defines.iter() {
+                builder = builder.define(name, value);
+            }
+
+            let shader = builder.build(self.gl.clone())?;
+            self.variants.insert(defines.clone(), shader);
+        }
+
+        Ok(&self.variants[defines])
+    }
+    /// Returns an already compiled variant without compiling anything
+    pub fn get_compiled(&self, defines: &ShaderDefines) -> Option<&Shader> {
+        self.variants.get(defines)
+    }
+    /// Drops all compiled variants, for example after the sources changed on disk
+    pub fn clear(&mut self) {
+        self.variants.clear();
+    }
+}
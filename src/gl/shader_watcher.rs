@@ -0,0 +1,81 @@
+use super::*;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Watches the source files of a shader, including the ones it pulls in through `#include`, and
+/// rebuilds it when they change on disk. Meant to be polled once per frame during development
+pub struct ShaderWatcher {
+    builder: ShaderBuilder,
+    watched_files: Vec<PathBuf>,
+    /// Most recent modification time seen among the watched files
+    last_modified: Option<SystemTime>,
+    gl: std::rc::Rc<Gl>,
+}
+
+impl ShaderWatcher {
+    pub fn new(builder: ShaderBuilder, gl: std::rc::Rc<Gl>) -> Self {
+        let mut res = Self {
+            builder,
+            watched_files: Vec::new(),
+            last_modified: None,
+            gl,
+        };
+
+        res.refresh_watched_files();
+        res.last_modified = res.latest_modification();
+
+        res
+    }
+
+    /// Rebuilds the shader if any of the watched files changed since the last poll, and swaps
+    /// the new program into the given shader. If the build fails, the error is printed and the
+    /// previous program is kept. Returns true if the shader was replaced
+    pub fn poll(&mut self, shader: &mut Shader) -> bool {
+        let modified = match self.latest_modification() {
+            Some(modified) => modified,
+            // Files can be briefly missing while an editor is saving them
+            None => return false,
+        };
+
+        if Some(modified) <= self.last_modified {
+            return false;
+        }
+        self.last_modified = Some(modified);
+
+        // Includes may have been added or removed by the edit
+        self.refresh_watched_files();
+
+        match self.builder.build(self.gl.clone()) {
+            Ok(new_shader) => {
+                // The old program is deleted when it is dropped here
+                *shader = new_shader;
+                println!("Reloaded shader from {:?}", self.builder.source_files());
+                true
+            }
+            Err(e) => {
+                eprintln!("Shader reload failed, keeping previous program: {}", e);
+                false
+            }
+        }
+    }
+
+    fn refresh_watched_files(&mut self) {
+        self.watched_files = self
+            .builder
+            .dependencies()
+            .unwrap_or_else(|_| self.builder.source_files());
+    }
+
+    fn latest_modification(&self) -> Option<SystemTime> {
+        self.watched_files
+            .iter()
+            .map(|path| modification_time(path))
+            .collect::<Option<Vec<_>>>()?
+            .into_iter()
+            .max()
+    }
+}
+
+fn modification_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
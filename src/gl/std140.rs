@@ -0,0 +1,282 @@
+/// A type that can be written into a uniform buffer following the std140 layout rules
+pub trait Std140 {
+    /// Base alignment of the type in bytes
+    const ALIGNMENT: usize;
+    fn write_std140(&self, writer: &mut Std140Writer);
+}
+
+/// Accumulates bytes laid out according to std140, inserting the padding the rules demand
+#[derive(Debug, Default)]
+pub struct Std140Writer {
+    bytes: Vec<u8>,
+}
+
+impl Std140Writer {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    /// Writes a value at the next offset matching its alignment
+    pub fn write<T: Std140 + ?Sized>(&mut self, value: &T) {
+        self.align_to(T::ALIGNMENT);
+        value.write_std140(self);
+    }
+    /// Writes raw bytes at the current offset. Used by the implementations of the basic types
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+    pub fn align_to(&mut self, alignment: usize) {
+        let padded = (self.bytes.len() + alignment - 1) / alignment * alignment;
+        self.bytes.resize(padded, 0);
+    }
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Serializes a value to its std140 representation
+pub fn to_std140_bytes<T: Std140 + ?Sized>(value: &T) -> Vec<u8> {
+    let mut writer = Std140Writer::new();
+    writer.write(value);
+    // Block sizes are multiples of a vec4
+    writer.align_to(16);
+    writer.into_bytes()
+}
+
+macro_rules! impl_std140_scalar {
+    ($t:ty) => {
+        impl Std140 for $t {
+            const ALIGNMENT: usize = 4;
+            fn write_std140(&self, writer: &mut Std140Writer) {
+                writer.write_bytes(&self.to_ne_bytes());
+            }
+        }
+    };
+}
+
+impl_std140_scalar!(f32);
+impl_std140_scalar!(i32);
+impl_std140_scalar!(u32);
+
+/// GLSL bools are 4 bytes in std140
+impl Std140 for bool {
+    const ALIGNMENT: usize = 4;
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.write_bytes(&(*self as u32).to_ne_bytes());
+    }
+}
+
+macro_rules! impl_std140_vector {
+    ($t:ty, $alignment:expr) => {
+        impl Std140 for $t {
+            const ALIGNMENT: usize = $alignment;
+            fn write_std140(&self, writer: &mut Std140Writer) {
+                for component in self.iter() {
+                    writer.write_bytes(&component.to_ne_bytes());
+                }
+            }
+        }
+    };
+}
+
+// vec3 is aligned like a vec4 but only takes up 12 bytes, so a following scalar fills the gap
+impl_std140_vector!(glm::Vec2, 8);
+impl_std140_vector!(glm::Vec3, 16);
+impl_std140_vector!(glm::Vec4, 16);
+impl_std140_vector!(glm::IVec2, 8);
+impl_std140_vector!(glm::IVec3, 16);
+impl_std140_vector!(glm::IVec4, 16);
+impl_std140_vector!(glm::UVec2, 8);
+impl_std140_vector!(glm::UVec3, 16);
+impl_std140_vector!(glm::UVec4, 16);
+
+macro_rules! impl_std140_matrix {
+    ($t:ty) => {
+        /// Matrices are stored as arrays of column vectors, each padded to a vec4
+        impl Std140 for $t {
+            const ALIGNMENT: usize = 16;
+            fn write_std140(&self, writer: &mut Std140Writer) {
+                for column in self.column_iter() {
+                    writer.align_to(16);
+                    for component in column.iter() {
+                        writer.write_bytes(&component.to_ne_bytes());
+                    }
+                }
+                writer.align_to(16);
+            }
+        }
+    };
+}
+
+impl_std140_matrix!(glm::Mat2);
+impl_std140_matrix!(glm::Mat3);
+impl_std140_matrix!(glm::Mat4);
+
+/// Colors are written as normalized vec4s
+impl Std140 for super::Color {
+    const ALIGNMENT: usize = 16;
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.write(&glm::vec4(
+            self.r_f32(),
+            self.g_f32(),
+            self.b_f32(),
+            self.a_f32(),
+        ));
+    }
+}
+
+/// Array elements are padded to the size of a vec4 each
+impl<T: Std140, const N: usize> Std140 for [T; N] {
+    const ALIGNMENT: usize = 16;
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        for element in self.iter() {
+            writer.align_to(16);
+            writer.write(element);
+        }
+        writer.align_to(16);
+    }
+}
+
+/// Implements `Std140` for a struct by writing the listed fields in order. The fields have to be
+/// listed in the same order as the members of the GLSL block or struct they mirror
+///
+/// ```ignore
+/// struct Light {
+///     position: glm::Vec3,
+///     intensity: f32,
+/// }
+/// impl_std140!(Light { position, intensity });
+/// ```
+#[macro_export]
+macro_rules! impl_std140 {
+    ($t:ty { $($field:ident),* $(,)? }) => {
+        impl $crate::gl::Std140 for $t {
+            // Structs are aligned and padded like a vec4
+            const ALIGNMENT: usize = 16;
+            fn write_std140(&self, writer: &mut $crate::gl::Std140Writer) {
+                $(writer.write(&self.$field);)*
+                writer.align_to(16);
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads the bytes back as floats, so that padding shows up as zeros
+    fn floats<T: Std140>(value: &T) -> Vec<f32> {
+        to_std140_bytes(value)
+            .chunks_exact(4)
+            .map(|chunk| f32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect()
+    }
+
+    struct VectorThenScalar {
+        direction: glm::Vec3,
+        intensity: f32,
+    }
+    crate::impl_std140!(VectorThenScalar {
+        direction,
+        intensity
+    });
+
+    struct ScalarThenVec2 {
+        scale: f32,
+        offset: glm::Vec2,
+    }
+    crate::impl_std140!(ScalarThenVec2 { scale, offset });
+
+    struct ArrayThenScalar {
+        weights: [f32; 3],
+        bias: f32,
+    }
+    crate::impl_std140!(ArrayThenScalar { weights, bias });
+
+    struct Inner {
+        position: glm::Vec3,
+    }
+    crate::impl_std140!(Inner { position });
+
+    struct StructThenScalar {
+        inner: Inner,
+        radius: f32,
+    }
+    crate::impl_std140!(StructThenScalar { inner, radius });
+
+    struct ScalarThenStruct {
+        radius: f32,
+        inner: Inner,
+    }
+    crate::impl_std140!(ScalarThenStruct { radius, inner });
+
+    #[test]
+    fn scalar_fills_the_gap_after_vec3() {
+        let value = VectorThenScalar {
+            direction: glm::vec3(1.0, 2.0, 3.0),
+            intensity: 4.0,
+        };
+        assert_eq!(floats(&value), vec![1.0, 2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn vec2_is_aligned_to_8_bytes() {
+        let value = ScalarThenVec2 {
+            scale: 1.0,
+            offset: glm::vec2(2.0, 3.0),
+        };
+        assert_eq!(floats(&value), vec![1.0, 0.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn matrix_columns_have_a_stride_of_16() {
+        // glm takes the components row by row
+        let mat2 = glm::mat2(1.0, 3.0, 2.0, 4.0);
+        assert_eq!(floats(&mat2), vec![1.0, 2.0, 0.0, 0.0, 3.0, 4.0, 0.0, 0.0]);
+
+        let mat3 = glm::mat3(1.0, 4.0, 7.0, 2.0, 5.0, 8.0, 3.0, 6.0, 9.0);
+        assert_eq!(
+            floats(&mat3),
+            vec![1.0, 2.0, 3.0, 0.0, 4.0, 5.0, 6.0, 0.0, 7.0, 8.0, 9.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn array_stride_is_rounded_up_to_16() {
+        let value = ArrayThenScalar {
+            weights: [1.0, 2.0, 3.0],
+            bias: 4.0,
+        };
+        assert_eq!(
+            floats(&value),
+            vec![1.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 3.0, 0.0, 0.0, 0.0, 4.0, 0.0, 0.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn structs_are_aligned_and_padded_to_16() {
+        let value = StructThenScalar {
+            inner: Inner {
+                position: glm::vec3(1.0, 2.0, 3.0),
+            },
+            radius: 4.0,
+        };
+        assert_eq!(floats(&value), vec![1.0, 2.0, 3.0, 0.0, 4.0, 0.0, 0.0, 0.0]);
+
+        let value = ScalarThenStruct {
+            radius: 4.0,
+            inner: Inner {
+                position: glm::vec3(1.0, 2.0, 3.0),
+            },
+        };
+        assert_eq!(floats(&value), vec![4.0, 0.0, 0.0, 0.0, 1.0, 2.0, 3.0, 0.0]);
+    }
+}
@@ -0,0 +1,298 @@
+use super::*;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+
+/// A region of a `StreamBuffer` handed out for this frame's data
+#[derive(Debug, Clone, Copy)]
+#[allow(dead_code)]
+pub struct StreamAllocation {
+    /// Byte offset of the data within the buffer
+    pub offset: usize,
+    pub size: usize,
+    /// Index of the first element within the buffer, i.e. the first vertex to draw from. The
+    /// offset is always a multiple of the element size so this is exact
+    pub first_element: usize,
+}
+
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum StreamBufferError {
+    /// The data is larger than the whole buffer
+    AllocationTooLarge { size: usize, capacity: usize },
+    /// The driver refused to map the buffer for writing
+    MapFailed,
+}
+
+impl std::error::Error for StreamBufferError {}
+impl std::fmt::Display for StreamBufferError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let to_write = match self {
+            StreamBufferError::AllocationTooLarge { size, capacity } => std::format!(
+                "Tried to stream {} bytes into a stream buffer of {} bytes",
+                size,
+                capacity
+            ),
+            StreamBufferError::MapFailed => String::from("Could not map stream buffer for writing"),
+        };
+        write!(f, "{}", to_write)
+    }
+}
+
+/// The bytes one finished frame occupies in the ring, kept until the GPU is done with them
+#[allow(dead_code)]
+struct FrameFence {
+    size: usize,
+    sync: types::GLsync,
+}
+
+/// Hands out per-frame regions of one large vertex buffer, used as a ring. Data written through
+/// it never reallocates the buffer. On Core43 fence sync objects make sure a region is only
+/// reused once the GPU has finished the frames reading it. ES3 falls back to orphaning the buffer
+/// whenever the ring wraps around, so there an allocation has to be drawn from before the next
+/// `push`
+#[allow(dead_code)]
+pub struct StreamBuffer {
+    vbo: ArrayVbo,
+    profile: GlProfile,
+    /// Byte offset the next allocation starts searching from
+    head: Cell<usize>,
+    /// Bytes in use by the current frame and the frames still in flight
+    used: Cell<usize>,
+    /// Bytes taken up by the current frame so far
+    frame_size: Cell<usize>,
+    /// Fences of the frames in flight, oldest first
+    frame_fences: RefCell<VecDeque<FrameFence>>,
+    /// Store a Rc to the Gl instance to ensure that we can destroy this resource when dropped
+    gl: std::rc::Rc<Gl>,
+}
+
+/// Where an allocation lands in the ring
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RingPlacement {
+    offset: usize,
+    /// Bytes the allocation takes up, including the alignment gap before it and, when wrapping,
+    /// the unused tail end of the ring
+    required: usize,
+    wrapped: bool,
+}
+
+/// Places `size` bytes at the first multiple of `element_size` from `head`, or at the start of the
+/// ring if they do not fit before its end
+fn place_in_ring(head: usize, size: usize, element_size: usize, capacity: usize) -> RingPlacement {
+    let offset = (head + element_size - 1) / element_size * element_size;
+    if offset + size > capacity {
+        // Wrap around, leaving the tail end of the buffer unused for this lap
+        return RingPlacement {
+            offset: 0,
+            required: capacity - head + size,
+            wrapped: true,
+        };
+    }
+    RingPlacement {
+        offset,
+        required: offset - head + size,
+        wrapped: false,
+    }
+}
+
+#[allow(dead_code)]
+impl StreamBuffer {
+    pub fn new(capacity: usize, profile: GlProfile, gl: std::rc::Rc<Gl>) -> Self {
+        let vbo = ArrayVbo::new(gl.clone()).with_growth_policy(BufferGrowthPolicy::Exact);
+        vbo.reserve(STREAM_DRAW, capacity, &gl);
+
+        Self {
+            vbo,
+            profile,
+            head: Cell::new(0),
+            used: Cell::new(0),
+            frame_size: Cell::new(0),
+            frame_fences: RefCell::new(VecDeque::new()),
+            gl,
+        }
+    }
+    /// The underlying buffer, to build a Vao from
+    pub fn vbo(&self) -> &ArrayVbo {
+        &self.vbo
+    }
+    pub fn capacity(&self) -> usize {
+        self.vbo.capacity()
+    }
+    /// Copies data into the ring and returns where it ended up. Draw calls should start at
+    /// `first_element` of the returned allocation. On ES3 the draw calls have to be issued before
+    /// the next push, which may orphan the buffer and with it the data of this allocation
+    pub fn push<T>(&self, data: &[T], gl: &Gl) -> Result<StreamAllocation, StreamBufferError> {
+        let bytes = unsafe {
+            std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data))
+        };
+        let element_size = std::mem::size_of::<T>().max(1);
+
+        let offset = self.allocate(bytes.len(), element_size, gl)?;
+        self.write(offset, bytes, gl)?;
+
+        Ok(StreamAllocation {
+            offset,
+            size: bytes.len(),
+            first_element: offset / element_size,
+        })
+    }
+    /// Marks the end of the frame's draw calls. Everything pushed since the last call is
+    /// protected from being overwritten until the GPU has executed those draw calls
+    pub fn end_frame(&self, gl: &Gl) {
+        let size = self.frame_size.replace(0);
+        if size == 0 || self.profile != GlProfile::Core43 {
+            return;
+        }
+
+        let sync = unsafe { gl.FenceSync(SYNC_GPU_COMMANDS_COMPLETE, 0) };
+        self.frame_fences
+            .borrow_mut()
+            .push_back(FrameFence { size, sync });
+    }
+    /// Finds room for `size` bytes at an offset that is a multiple of `element_size`, waiting for
+    /// in-flight frames to finish if the ring is full
+    fn allocate(
+        &self,
+        size: usize,
+        element_size: usize,
+        gl: &Gl,
+    ) -> Result<usize, StreamBufferError> {
+        let capacity = self.capacity();
+        if size > capacity {
+            return Err(StreamBufferError::AllocationTooLarge { size, capacity });
+        }
+
+        let placement = place_in_ring(self.head.get(), size, element_size, capacity);
+        let offset = placement.offset;
+        let mut required = placement.required;
+
+        // Without fences the old storage is handed back to the driver instead, which keeps it
+        // alive for draw calls already issued. Allocations not drawn from yet are lost with it
+        if placement.wrapped && self.profile != GlProfile::Core43 {
+            self.vbo.orphan(gl);
+            self.used.set(0);
+            required = size;
+        }
+
+        if self.profile == GlProfile::Core43 {
+            while capacity - self.used.get() < required {
+                if !self.wait_for_oldest_frame(gl) {
+                    // Only the current frame is left, which already fills the ring
+                    return Err(StreamBufferError::AllocationTooLarge { size, capacity });
+                }
+            }
+        }
+
+        self.head.set(offset + size);
+        self.used.set(self.used.get() + required);
+        self.frame_size.set(self.frame_size.get() + required);
+        Ok(offset)
+    }
+    /// Blocks until the oldest in-flight frame is done and releases its region. Returns false if
+    /// there was none
+    fn wait_for_oldest_frame(&self, gl: &Gl) -> bool {
+        let frame = match self.frame_fences.borrow_mut().pop_front() {
+            Some(frame) => frame,
+            None => return false,
+        };
+
+        loop {
+            let result =
+                unsafe { gl.ClientWaitSync(frame.sync, SYNC_FLUSH_COMMANDS_BIT, 1_000_000) };
+            if result != TIMEOUT_EXPIRED {
+                break;
+            }
+        }
+        unsafe { gl.DeleteSync(frame.sync) };
+
+        self.used.set(self.used.get() - frame.size);
+        true
+    }
+    fn write(&self, offset: usize, bytes: &[u8], gl: &Gl) -> Result<(), StreamBufferError> {
+        if self.profile != GlProfile::Core43 {
+            //unwrap since allocate only hands out ranges within the buffer
+            self.vbo.update_array_vbo_raw(offset, bytes, gl).unwrap();
+            return Ok(());
+        }
+
+        // The fences already guarantee the GPU is done with the region, so the driver does not
+        // need to synchronize
+        self.vbo.bind(gl);
+        unsafe {
+            let mapped = gl.MapBufferRange(
+                ARRAY_BUFFER,
+                offset as isize,
+                bytes.len() as isize,
+                MAP_WRITE_BIT | MAP_UNSYNCHRONIZED_BIT | MAP_INVALIDATE_RANGE_BIT,
+            );
+            if mapped.is_null() {
+                return Err(StreamBufferError::MapFailed);
+            }
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), mapped as *mut u8, bytes.len());
+            gl.UnmapBuffer(ARRAY_BUFFER);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for StreamBuffer {
+    fn drop(&mut self) {
+        for frame in self.frame_fences.borrow_mut().drain(..) {
+            unsafe {
+                self.gl.DeleteSync(frame.sync);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn place_in_ring_aligns_to_element_size() {
+        let placement = place_in_ring(10, 12, 12, 100);
+        assert_eq!(
+            placement,
+            RingPlacement {
+                offset: 12,
+                required: 14,
+                wrapped: false
+            }
+        );
+    }
+
+    #[test]
+    fn place_in_ring_fills_the_ring_exactly() {
+        let placement = place_in_ring(88, 12, 4, 100);
+        assert_eq!(
+            placement,
+            RingPlacement {
+                offset: 88,
+                required: 12,
+                wrapped: false
+            }
+        );
+    }
+
+    #[test]
+    fn place_in_ring_wraps_and_counts_the_skipped_tail() {
+        let placement = place_in_ring(90, 12, 4, 100);
+        assert_eq!(
+            placement,
+            RingPlacement {
+                offset: 0,
+                required: 22,
+                wrapped: true
+            }
+        );
+    }
+
+    #[test]
+    fn place_in_ring_wraps_when_alignment_pushes_past_the_end() {
+        let placement = place_in_ring(85, 12, 12, 96);
+        assert!(placement.wrapped);
+        assert_eq!(placement.offset, 0);
+        assert_eq!(placement.required, 96 - 85 + 12);
+    }
+}
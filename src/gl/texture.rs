@@ -0,0 +1,492 @@
+use super::*;
+
+/// From EXT_texture_filter_anisotropic, which is not part of the GL 4.3 bindings
+pub const TEXTURE_MAX_ANISOTROPY_EXT: types::GLenum = 0x84FE;
+pub const MAX_TEXTURE_MAX_ANISOTROPY_EXT: types::GLenum = 0x84FF;
+
+/// Internal formats available on both ES3 and Core43
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(dead_code)]
+pub enum TextureFormat {
+    R8,
+    RG8,
+    RGB8,
+    RGBA8,
+    /// RGBA8 with the color channels in sRGB space, converted to linear when sampled
+    SRGB8Alpha8,
+    R16F,
+    RGBA16F,
+    RGBA32F,
+    Depth16,
+    Depth24,
+    Depth32F,
+    Depth24Stencil8,
+}
+
+#[allow(dead_code)]
+impl TextureFormat {
+    pub fn internal_format(&self) -> types::GLenum {
+        match self {
+            TextureFormat::R8 => R8,
+            TextureFormat::RG8 => RG8,
+            TextureFormat::RGB8 => RGB8,
+            TextureFormat::RGBA8 => RGBA8,
+            TextureFormat::SRGB8Alpha8 => SRGB8_ALPHA8,
+            TextureFormat::R16F => R16F,
+            TextureFormat::RGBA16F => RGBA16F,
+            TextureFormat::RGBA32F => RGBA32F,
+            TextureFormat::Depth16 => DEPTH_COMPONENT16,
+            TextureFormat::Depth24 => DEPTH_COMPONENT24,
+            TextureFormat::Depth32F => DEPTH_COMPONENT32F,
+            TextureFormat::Depth24Stencil8 => DEPTH24_STENCIL8,
+        }
+    }
+    /// Format of the pixel data handed to uploads
+    pub fn pixel_format(&self) -> types::GLenum {
+        match self {
+            TextureFormat::R8 | TextureFormat::R16F => RED,
+            TextureFormat::RG8 => RG,
+            TextureFormat::RGB8 => RGB,
+            TextureFormat::RGBA8
+            | TextureFormat::SRGB8Alpha8
+            | TextureFormat::RGBA16F
+            | TextureFormat::RGBA32F => RGBA,
+            TextureFormat::Depth16 | TextureFormat::Depth24 | TextureFormat::Depth32F => {
+                DEPTH_COMPONENT
+            }
+            TextureFormat::Depth24Stencil8 => DEPTH_STENCIL,
+        }
+    }
+    /// Component type of the pixel data handed to uploads
+    pub fn pixel_type(&self) -> types::GLenum {
+        match self {
+            TextureFormat::R8
+            | TextureFormat::RG8
+            | TextureFormat::RGB8
+            | TextureFormat::RGBA8
+            | TextureFormat::SRGB8Alpha8 => UNSIGNED_BYTE,
+            TextureFormat::R16F | TextureFormat::RGBA16F => HALF_FLOAT,
+            TextureFormat::RGBA32F | TextureFormat::Depth32F => FLOAT,
+            TextureFormat::Depth16 => UNSIGNED_SHORT,
+            TextureFormat::Depth24 => UNSIGNED_INT,
+            TextureFormat::Depth24Stencil8 => UNSIGNED_INT_24_8,
+        }
+    }
+    /// Size in bytes of one pixel of upload data
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            TextureFormat::R8 => 1,
+            TextureFormat::RG8 | TextureFormat::R16F | TextureFormat::Depth16 => 2,
+            TextureFormat::RGB8 => 3,
+            TextureFormat::RGBA8
+            | TextureFormat::SRGB8Alpha8
+            | TextureFormat::Depth24
+            | TextureFormat::Depth32F
+            | TextureFormat::Depth24Stencil8 => 4,
+            TextureFormat::RGBA16F => 8,
+            TextureFormat::RGBA32F => 16,
+        }
+    }
+    pub fn is_depth(&self) -> bool {
+        matches!(
+            self,
+            TextureFormat::Depth16
+                | TextureFormat::Depth24
+                | TextureFormat::Depth32F
+                | TextureFormat::Depth24Stencil8
+        )
+    }
+    /// Mipmaps can only be generated for formats that are both color-renderable and filterable
+    /// on every supported profile. Float formats are only color-renderable on ES3 with
+    /// EXT_color_buffer_float
+    pub fn supports_mipmaps(&self) -> bool {
+        !self.is_depth()
+            && !matches!(
+                self,
+                TextureFormat::R16F | TextureFormat::RGBA16F | TextureFormat::RGBA32F
+            )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum TextureFilter {
+    Nearest,
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum TextureWrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+}
+
+#[allow(dead_code)]
+impl TextureWrap {
+    pub fn gl_enum(&self) -> types::GLenum {
+        match self {
+            TextureWrap::Repeat => REPEAT,
+            TextureWrap::MirroredRepeat => MIRRORED_REPEAT,
+            TextureWrap::ClampToEdge => CLAMP_TO_EDGE,
+        }
+    }
+}
+
+/// How a texture is filtered and addressed when sampled
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub struct TextureSampling {
+    pub min_filter: TextureFilter,
+    pub mag_filter: TextureFilter,
+    /// Filter between mip levels. None samples only the base level
+    pub mipmap_filter: Option<TextureFilter>,
+    pub wrap_s: TextureWrap,
+    pub wrap_t: TextureWrap,
+    /// Maximum anisotropy, 1.0 to disable. Clamped to what the driver supports and ignored when
+    /// EXT_texture_filter_anisotropic is unavailable
+    pub anisotropy: f32,
+}
+
+impl Default for TextureSampling {
+    fn default() -> Self {
+        Self {
+            min_filter: TextureFilter::Linear,
+            mag_filter: TextureFilter::Linear,
+            mipmap_filter: None,
+            wrap_s: TextureWrap::Repeat,
+            wrap_t: TextureWrap::Repeat,
+            anisotropy: 1.0,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl TextureSampling {
+    /// Nearest filtering without mipmaps, for pixel art and lookup tables
+    pub fn nearest() -> Self {
+        Self {
+            min_filter: TextureFilter::Nearest,
+            mag_filter: TextureFilter::Nearest,
+            ..Default::default()
+        }
+    }
+    /// Trilinear filtering, for mipmapped textures
+    pub fn trilinear() -> Self {
+        Self {
+            mipmap_filter: Some(TextureFilter::Linear),
+            ..Default::default()
+        }
+    }
+    fn min_filter_gl_enum(&self) -> types::GLenum {
+        match (self.min_filter, self.mipmap_filter) {
+            (TextureFilter::Nearest, None) => NEAREST,
+            (TextureFilter::Linear, None) => LINEAR,
+            (TextureFilter::Nearest, Some(TextureFilter::Nearest)) => NEAREST_MIPMAP_NEAREST,
+            (TextureFilter::Linear, Some(TextureFilter::Nearest)) => LINEAR_MIPMAP_NEAREST,
+            (TextureFilter::Nearest, Some(TextureFilter::Linear)) => NEAREST_MIPMAP_LINEAR,
+            (TextureFilter::Linear, Some(TextureFilter::Linear)) => LINEAR_MIPMAP_LINEAR,
+        }
+    }
+    fn mag_filter_gl_enum(&self) -> types::GLenum {
+        match self.mag_filter {
+            TextureFilter::Nearest => NEAREST,
+            TextureFilter::Linear => LINEAR,
+        }
+    }
+}
+
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum TextureError {
+    /// The pixel data does not have the size the region and format call for
+    DataSizeMismatch { expected: usize, actual: usize },
+    /// The region reaches outside of the texture
+    RegionOutOfBounds {
+        offset: glm::UVec2,
+        size: glm::UVec2,
+        texture_size: glm::UVec2,
+    },
+    /// Mipmaps were requested for a format that cannot have them generated
+    MipmapsUnsupported(TextureFormat),
+}
+
+impl std::error::Error for TextureError {}
+impl std::fmt::Display for TextureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let to_write = match self {
+            TextureError::DataSizeMismatch { expected, actual } => std::format!(
+                "Texture data is {} bytes but the upload needs {}",
+                actual,
+                expected
+            ),
+            TextureError::RegionOutOfBounds {
+                offset,
+                size,
+                texture_size,
+            } => std::format!(
+                "Region of size {}x{} at {},{} is outside of the {}x{} texture",
+                size.x,
+                size.y,
+                offset.x,
+                offset.y,
+                texture_size.x,
+                texture_size.y
+            ),
+            TextureError::MipmapsUnsupported(format) => {
+                std::format!(
+                    "Cannot generate mipmaps for textures of format {:?}",
+                    format
+                )
+            }
+        };
+        write!(f, "{}", to_write)
+    }
+}
+
+/// A 2D texture with immutable storage of a fixed size and format
+#[allow(dead_code)]
+pub struct Texture2D {
+    gl_handle: u32,
+    size: glm::UVec2,
+    format: TextureFormat,
+    mip_level_count: i32,
+    /// Store a Rc to the Gl instance to ensure that we can destroy this resource when dropped
+    gl: std::rc::Rc<Gl>,
+}
+
+#[allow(dead_code)]
+impl Texture2D {
+    /// Allocates a texture with undefined content. With `mipmaps` storage for the full mip chain
+    /// is allocated, to be filled by `generate_mipmaps`
+    pub fn new(
+        size: glm::UVec2,
+        format: TextureFormat,
+        mipmaps: bool,
+        gl: std::rc::Rc<Gl>,
+    ) -> Result<Self, TextureError> {
+        if mipmaps && !format.supports_mipmaps() {
+            return Err(TextureError::MipmapsUnsupported(format));
+        }
+
+        let mip_level_count = if mipmaps {
+            32 - size.x.max(size.y).max(1).leading_zeros() as i32
+        } else {
+            1
+        };
+
+        let mut gl_handle: types::GLuint = 0;
+        unsafe {
+            gl.GenTextures(1, &mut gl_handle as *mut _);
+        }
+        assert!(gl_handle > 0);
+
+        let res = Self {
+            gl_handle,
+            size,
+            format,
+            mip_level_count,
+            gl,
+        };
+
+        res.bind(&res.gl);
+        unsafe {
+            res.gl.TexStorage2D(
+                TEXTURE_2D,
+                mip_level_count,
+                format.internal_format(),
+                size.x as i32,
+                size.y as i32,
+            );
+        }
+        // The GL defaults expect a mip chain, which would leave textures without one incomplete.
+        // Depth and 32 bit float textures are not filterable on ES3
+        let sampling = if mipmaps {
+            TextureSampling::trilinear()
+        } else if format.is_depth() || format == TextureFormat::RGBA32F {
+            TextureSampling::nearest()
+        } else {
+            TextureSampling::default()
+        };
+        res.apply_sampling(&sampling, &res.gl);
+        Self::unbind(&res.gl);
+
+        Ok(res)
+    }
+    /// Creates a texture and fills its base level with `data`
+    pub fn from_data(
+        size: glm::UVec2,
+        format: TextureFormat,
+        data: &[u8],
+        mipmaps: bool,
+        gl: std::rc::Rc<Gl>,
+    ) -> Result<Self, TextureError> {
+        let res = Self::new(size, format, mipmaps, gl)?;
+        res.upload(data, &res.gl)?;
+        if mipmaps {
+            res.generate_mipmaps(&res.gl);
+        }
+        Ok(res)
+    }
+
+    pub fn gl_handle(&self) -> types::GLuint {
+        self.gl_handle
+    }
+    pub fn size(&self) -> glm::UVec2 {
+        self.size
+    }
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+    pub fn mip_level_count(&self) -> i32 {
+        self.mip_level_count
+    }
+    pub fn bind(&self, gl: &super::Gl) {
+        unsafe {
+            gl.BindTexture(TEXTURE_2D, self.gl_handle);
+        }
+    }
+    pub fn unbind(gl: &super::Gl) {
+        unsafe {
+            gl.BindTexture(TEXTURE_2D, 0);
+        }
+    }
+    /// Replaces the content of the base level. Rows are tightly packed, starting at the first row
+    /// in memory
+    pub fn upload(&self, data: &[u8], gl: &super::Gl) -> Result<(), TextureError> {
+        self.upload_sub_image(glm::vec2(0, 0), self.size, data, gl)
+    }
+    /// Replaces a region of the base level
+    pub fn upload_sub_image(
+        &self,
+        offset: glm::UVec2,
+        size: glm::UVec2,
+        data: &[u8],
+        gl: &super::Gl,
+    ) -> Result<(), TextureError> {
+        let end_x = offset.x.checked_add(size.x);
+        let end_y = offset.y.checked_add(size.y);
+        let inside = match (end_x, end_y) {
+            (Some(end_x), Some(end_y)) => end_x <= self.size.x && end_y <= self.size.y,
+            _ => false,
+        };
+        if !inside {
+            return Err(TextureError::RegionOutOfBounds {
+                offset,
+                size,
+                texture_size: self.size,
+            });
+        }
+
+        let expected = (size.x * size.y) as usize * self.format.bytes_per_pixel();
+        if data.len() != expected {
+            return Err(TextureError::DataSizeMismatch {
+                expected,
+                actual: data.len(),
+            });
+        }
+
+        self.bind(gl);
+        unsafe {
+            // Rows of formats with less than 4 bytes per pixel are not necessarily 4 byte aligned
+            gl.PixelStorei(UNPACK_ALIGNMENT, 1);
+            gl.TexSubImage2D(
+                TEXTURE_2D,
+                0,
+                offset.x as i32,
+                offset.y as i32,
+                size.x as i32,
+                size.y as i32,
+                self.format.pixel_format(),
+                self.format.pixel_type(),
+                data.as_ptr() as *const std::ffi::c_void,
+            );
+            gl.PixelStorei(UNPACK_ALIGNMENT, 4);
+        }
+        Self::unbind(gl);
+        Ok(())
+    }
+    /// Fills the mip levels below the base level from it. Only has an effect on textures created
+    /// with mipmaps
+    pub fn generate_mipmaps(&self, gl: &super::Gl) {
+        if self.mip_level_count <= 1 {
+            return;
+        }
+
+        self.bind(gl);
+        unsafe {
+            gl.GenerateMipmap(TEXTURE_2D);
+        }
+        Self::unbind(gl);
+    }
+    pub fn set_sampling(&self, sampling: &TextureSampling, gl: &super::Gl) {
+        self.bind(gl);
+        self.apply_sampling(sampling, gl);
+        Self::unbind(gl);
+    }
+    /// Sets the sampling parameters on the bound texture
+    fn apply_sampling(&self, sampling: &TextureSampling, gl: &super::Gl) {
+        unsafe {
+            gl.TexParameteri(
+                TEXTURE_2D,
+                TEXTURE_MIN_FILTER,
+                sampling.min_filter_gl_enum() as i32,
+            );
+            gl.TexParameteri(
+                TEXTURE_2D,
+                TEXTURE_MAG_FILTER,
+                sampling.mag_filter_gl_enum() as i32,
+            );
+            gl.TexParameteri(TEXTURE_2D, TEXTURE_WRAP_S, sampling.wrap_s.gl_enum() as i32);
+            gl.TexParameteri(TEXTURE_2D, TEXTURE_WRAP_T, sampling.wrap_t.gl_enum() as i32);
+        }
+
+        if let Some(max_anisotropy) = max_anisotropy(gl) {
+            unsafe {
+                gl.TexParameterf(
+                    TEXTURE_2D,
+                    TEXTURE_MAX_ANISOTROPY_EXT,
+                    sampling.anisotropy.max(1.0).min(max_anisotropy),
+                );
+            }
+        }
+    }
+}
+
+thread_local! {
+    /// Result of `max_anisotropy`, queried on first use. GL contexts are current on a single
+    /// thread, and the game only creates one
+    static MAX_ANISOTROPY: std::cell::Cell<Option<Option<f32>>> = std::cell::Cell::new(None);
+}
+
+/// The highest anisotropy the context supports, or None without anisotropic filtering. Scanning
+/// the extension list is slow, so this is only done once
+fn max_anisotropy(gl: &Gl) -> Option<f32> {
+    if let Some(cached) = MAX_ANISOTROPY.with(|cached| cached.get()) {
+        return cached;
+    }
+
+    let max_anisotropy = if has_extension("GL_EXT_texture_filter_anisotropic", gl) {
+        let mut max_anisotropy: f32 = 1.0;
+        unsafe {
+            gl.GetFloatv(
+                MAX_TEXTURE_MAX_ANISOTROPY_EXT,
+                &mut max_anisotropy as *mut f32,
+            );
+        }
+        Some(max_anisotropy)
+    } else {
+        None
+    };
+    MAX_ANISOTROPY.with(|cached| cached.set(Some(max_anisotropy)));
+    max_anisotropy
+}
+
+impl Drop for Texture2D {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.DeleteTextures(1, &self.gl_handle as *const _);
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use super::*;
+use std::cell::Cell;
+
+/// A buffer holding the data of a uniform block. Bound to a binding point, it can be shared by
+/// every shader that has its block bound to the same point
+pub struct UniformBuffer {
+    gl_handle: u32,
+    /// Size in bytes of the storage currently allocated on the GPU
+    capacity: Cell<usize>,
+    /// Usage hint the storage was allocated with
+    usage: Cell<types::GLenum>,
+    /// Store a Rc to the Gl instance to ensure that we can destroy this resource when dropped
+    gl: std::rc::Rc<Gl>,
+}
+
+impl UniformBuffer {
+    pub fn new(gl: std::rc::Rc<Gl>) -> Self {
+        let mut gl_handle: types::GLuint = 0;
+        unsafe {
+            gl.GenBuffers(1, &mut gl_handle as *mut _);
+        }
+        assert!(gl_handle > 0);
+        Self {
+            gl_handle,
+            capacity: Cell::new(0),
+            usage: Cell::new(STATIC_DRAW),
+            gl,
+        }
+    }
+
+    pub fn bind(&self, gl: &super::Gl) {
+        unsafe {
+            gl.BindBuffer(super::UNIFORM_BUFFER, self.gl_handle);
+        }
+    }
+    pub fn unbind(gl: &super::Gl) {
+        unsafe {
+            gl.BindBuffer(super::UNIFORM_BUFFER, 0);
+        }
+    }
+    /// Makes the buffer the source of all uniform blocks bound to the given binding point
+    pub fn bind_to_binding_point(&self, binding_point: u32, gl: &super::Gl) {
+        unsafe {
+            gl.BindBufferBase(super::UNIFORM_BUFFER, binding_point, self.gl_handle);
+        }
+    }
+    pub fn upload_std140<T: Std140 + ?Sized>(
+        &self,
+        usage: crate::gl::types::GLenum,
+        value: &T,
+        gl: &crate::gl::Gl,
+    ) {
+        self.upload_raw(usage, &to_std140_bytes(value), gl);
+    }
+    /// Replaces the content of the buffer. The existing storage is reused if the data fits and
+    /// the usage matches, so that per-frame uploads do not reallocate
+    pub fn upload_raw(&self, usage: crate::gl::types::GLenum, bytes: &[u8], gl: &crate::gl::Gl) {
+        self.bind(gl);
+        unsafe {
+            if usage == self.usage.get() && bytes.len() <= self.capacity.get() {
+                gl.BufferSubData(
+                    crate::gl::UNIFORM_BUFFER,
+                    0,
+                    bytes.len() as isize,
+                    bytes.as_ptr() as *const std::ffi::c_void,
+                );
+            } else {
+                gl.BufferData(
+                    crate::gl::UNIFORM_BUFFER,
+                    bytes.len() as isize,
+                    bytes.as_ptr() as *const std::ffi::c_void,
+                    usage,
+                );
+                self.capacity.set(bytes.len());
+                self.usage.set(usage);
+            }
+        }
+        Self::unbind(gl);
+    }
+}
+
+impl Drop for UniformBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.DeleteBuffers(1, &self.gl_handle as *const _);
+        }
+    }
+}
@@ -0,0 +1,323 @@
+use super::*;
+
+/// Describes an active uniform of a linked shader program as reported by the driver
+#[derive(Debug, Clone)]
+pub struct UniformInfo {
+    /// Name of the uniform, with any trailing "[0]" of array uniforms removed
+    pub name: String,
+    /// GL type of the uniform, i.e. FLOAT_VEC3, FLOAT_MAT4, SAMPLER_2D
+    pub data_type: types::GLenum,
+    /// Amount of array elements. 1 for non-array uniforms
+    pub size: i32,
+    pub location: types::GLint,
+}
+
+/// A value that can be uploaded to a shader uniform
+pub trait UniformValue {
+    /// Returns true if this value can be uploaded to a uniform of the given GL type
+    fn is_compatible(data_type: types::GLenum) -> bool;
+    /// Amount of array elements this value covers
+    fn count(&self) -> i32 {
+        1
+    }
+    /// Uploads the value to the given location of the currently bound program
+    fn upload(&self, location: types::GLint, gl: &Gl);
+}
+
+macro_rules! impl_uniform_value {
+    ($t:ty, $gl_type:expr, |$loc:ident, $count:ident, $ptr:ident, $gl:ident| $upload:expr) => {
+        impl UniformValue for $t {
+            fn is_compatible(data_type: types::GLenum) -> bool {
+                data_type == $gl_type
+            }
+            fn upload(&self, $loc: types::GLint, $gl: &Gl) {
+                let $count = 1;
+                let $ptr = self as *const $t as *const _;
+                unsafe { $upload }
+            }
+        }
+        impl UniformValue for [$t] {
+            fn is_compatible(data_type: types::GLenum) -> bool {
+                data_type == $gl_type
+            }
+            fn count(&self) -> i32 {
+                self.len() as i32
+            }
+            fn upload(&self, $loc: types::GLint, $gl: &Gl) {
+                let $count = self.len() as i32;
+                let $ptr = self.as_ptr() as *const _;
+                unsafe { $upload }
+            }
+        }
+    };
+}
+
+impl_uniform_value!(f32, FLOAT, |l, c, p, gl| gl.Uniform1fv(l, c, p));
+impl_uniform_value!(u32, UNSIGNED_INT, |l, c, p, gl| gl.Uniform1uiv(l, c, p));
+impl_uniform_value!(glm::Vec2, FLOAT_VEC2, |l, c, p, gl| gl.Uniform2fv(l, c, p));
+impl_uniform_value!(glm::Vec3, FLOAT_VEC3, |l, c, p, gl| gl.Uniform3fv(l, c, p));
+impl_uniform_value!(glm::Vec4, FLOAT_VEC4, |l, c, p, gl| gl.Uniform4fv(l, c, p));
+impl_uniform_value!(glm::IVec2, INT_VEC2, |l, c, p, gl| gl.Uniform2iv(l, c, p));
+impl_uniform_value!(glm::IVec3, INT_VEC3, |l, c, p, gl| gl.Uniform3iv(l, c, p));
+impl_uniform_value!(glm::IVec4, INT_VEC4, |l, c, p, gl| gl.Uniform4iv(l, c, p));
+impl_uniform_value!(glm::Mat2, FLOAT_MAT2, |l, c, p, gl| gl
+    .UniformMatrix2fv(l, c, FALSE, p));
+impl_uniform_value!(glm::Mat3, FLOAT_MAT3, |l, c, p, gl| gl
+    .UniformMatrix3fv(l, c, FALSE, p));
+impl_uniform_value!(glm::Mat4, FLOAT_MAT4, |l, c, p, gl| gl
+    .UniformMatrix4fv(l, c, FALSE, p));
+
+// i32 is implemented by hand since int uniforms also cover booleans
+impl UniformValue for i32 {
+    fn is_compatible(data_type: types::GLenum) -> bool {
+        data_type == INT || data_type == BOOL
+    }
+    fn upload(&self, location: types::GLint, gl: &Gl) {
+        unsafe { gl.Uniform1i(location, *self) };
+    }
+}
+
+impl UniformValue for [i32] {
+    fn is_compatible(data_type: types::GLenum) -> bool {
+        i32::is_compatible(data_type)
+    }
+    fn count(&self) -> i32 {
+        self.len() as i32
+    }
+    fn upload(&self, location: types::GLint, gl: &Gl) {
+        unsafe { gl.Uniform1iv(location, self.len() as i32, self.as_ptr()) };
+    }
+}
+
+/// Colors are uploaded as normalized vec4s
+impl UniformValue for Color {
+    fn is_compatible(data_type: types::GLenum) -> bool {
+        data_type == FLOAT_VEC4
+    }
+    fn upload(&self, location: types::GLint, gl: &Gl) {
+        unsafe {
+            gl.Uniform4f(
+                location,
+                self.r_f32(),
+                self.g_f32(),
+                self.b_f32(),
+                self.a_f32(),
+            )
+        };
+    }
+}
+
+/// Enumerates all active uniforms of a linked program that have a location. Uniforms that are
+/// part of uniform blocks have no location and are skipped
+pub fn query_active_uniforms(
+    program_gl_handle: types::GLuint,
+    gl: &Gl,
+) -> std::collections::HashMap<String, UniformInfo> {
+    let mut uniforms = std::collections::HashMap::new();
+
+    let mut count: types::GLint = 0;
+    let mut max_name_length: types::GLint = 0;
+    unsafe {
+        gl.GetProgramiv(program_gl_handle, ACTIVE_UNIFORMS, &mut count as *mut i32);
+        gl.GetProgramiv(
+            program_gl_handle,
+            ACTIVE_UNIFORM_MAX_LENGTH,
+            &mut max_name_length as *mut i32,
+        );
+    }
+
+    for index in 0..count as u32 {
+        let mut name_buffer: Vec<u8> = vec![0; max_name_length as usize];
+        let mut name_length: types::GLsizei = 0;
+        let mut size: types::GLint = 0;
+        let mut data_type: types::GLenum = 0;
+
+        unsafe {
+            gl.GetActiveUniform(
+                program_gl_handle,
+                index,
+                max_name_length,
+                &mut name_length as *mut i32,
+                &mut size as *mut i32,
+                &mut data_type as *mut u32,
+                name_buffer.as_mut_ptr() as *mut types::GLchar,
+            )
+        };
+        name_buffer.truncate(name_length as usize);
+
+        let c_name = match std::ffi::CString::new(name_buffer) {
+            Ok(c_name) => c_name,
+            Err(_) => continue,
+        };
+        let location = unsafe { gl.GetUniformLocation(program_gl_handle, c_name.as_ptr()) };
+
+        if location < 0 {
+            continue;
+        }
+
+        let name = c_name.to_string_lossy();
+        let name = String::from(name.strip_suffix("[0]").unwrap_or(&name));
+
+        uniforms.insert(
+            name.clone(),
+            UniformInfo {
+                name,
+                data_type,
+                size,
+                location,
+            },
+        );
+    }
+
+    uniforms
+}
+
+/// Describes an active uniform block of a linked shader program
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct UniformBlockInfo {
+    pub name: String,
+    pub index: types::GLuint,
+    /// Minimum size in bytes of a buffer backing the block
+    pub data_size: i32,
+}
+
+pub fn query_active_uniform_blocks(
+    program_gl_handle: types::GLuint,
+    gl: &Gl,
+) -> std::collections::HashMap<String, UniformBlockInfo> {
+    let mut blocks = std::collections::HashMap::new();
+
+    let mut count: types::GLint = 0;
+    let mut max_name_length: types::GLint = 0;
+    unsafe {
+        gl.GetProgramiv(
+            program_gl_handle,
+            ACTIVE_UNIFORM_BLOCKS,
+            &mut count as *mut i32,
+        );
+        gl.GetProgramiv(
+            program_gl_handle,
+            ACTIVE_UNIFORM_BLOCK_MAX_NAME_LENGTH,
+            &mut max_name_length as *mut i32,
+        );
+    }
+
+    for index in 0..count as u32 {
+        let mut name_buffer: Vec<u8> = vec![0; max_name_length as usize];
+        let mut name_length: types::GLsizei = 0;
+        let mut data_size: types::GLint = 0;
+
+        unsafe {
+            gl.GetActiveUniformBlockName(
+                program_gl_handle,
+                index,
+                max_name_length,
+                &mut name_length as *mut i32,
+                name_buffer.as_mut_ptr() as *mut types::GLchar,
+            );
+            gl.GetActiveUniformBlockiv(
+                program_gl_handle,
+                index,
+                UNIFORM_BLOCK_DATA_SIZE,
+                &mut data_size as *mut i32,
+            );
+        }
+        name_buffer.truncate(name_length as usize);
+
+        let name = String::from_utf8_lossy(&name_buffer).into_owned();
+        blocks.insert(
+            name.clone(),
+            UniformBlockInfo {
+                name,
+                index,
+                data_size,
+            },
+        );
+    }
+
+    blocks
+}
+
+/// A sampler uniform of a linked program, along with the texture units it was assigned
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct SamplerInfo {
+    pub name: String,
+    /// Texture target the sampler reads from, i.e. TEXTURE_2D for sampler2D
+    pub target: types::GLenum,
+    /// First texture unit of the sampler. Sampler arrays occupy `size` consecutive units
+    pub unit: u32,
+    pub size: i32,
+}
+
+/// Returns the texture target a sampler uniform type reads from, or None if the type is not a
+/// sampler
+pub fn sampler_target(data_type: types::GLenum) -> Option<types::GLenum> {
+    match data_type {
+        SAMPLER_2D | SAMPLER_2D_SHADOW | INT_SAMPLER_2D | UNSIGNED_INT_SAMPLER_2D => {
+            Some(TEXTURE_2D)
+        }
+        SAMPLER_3D | INT_SAMPLER_3D | UNSIGNED_INT_SAMPLER_3D => Some(TEXTURE_3D),
+        SAMPLER_CUBE | SAMPLER_CUBE_SHADOW | INT_SAMPLER_CUBE | UNSIGNED_INT_SAMPLER_CUBE => {
+            Some(TEXTURE_CUBE_MAP)
+        }
+        SAMPLER_2D_ARRAY
+        | SAMPLER_2D_ARRAY_SHADOW
+        | INT_SAMPLER_2D_ARRAY
+        | UNSIGNED_INT_SAMPLER_2D_ARRAY => Some(TEXTURE_2D_ARRAY),
+        SAMPLER_2D_MULTISAMPLE
+        | INT_SAMPLER_2D_MULTISAMPLE
+        | UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE => Some(TEXTURE_2D_MULTISAMPLE),
+        SAMPLER_BUFFER | INT_SAMPLER_BUFFER | UNSIGNED_INT_SAMPLER_BUFFER => Some(TEXTURE_BUFFER),
+        _ => None,
+    }
+}
+
+/// Gives every sampler uniform of a linked program its own fixed texture units, in name order so
+/// that the assignment is stable between builds of the same source
+pub fn assign_sampler_units(
+    program_gl_handle: types::GLuint,
+    uniforms: &std::collections::HashMap<String, UniformInfo>,
+    gl: &Gl,
+) -> std::collections::HashMap<String, SamplerInfo> {
+    let mut sampler_uniforms: Vec<&UniformInfo> = uniforms
+        .values()
+        .filter(|uniform| sampler_target(uniform.data_type).is_some())
+        .collect();
+    sampler_uniforms.sort_by(|a, b| a.name.cmp(&b.name));
+
+    if sampler_uniforms.is_empty() {
+        return std::collections::HashMap::new();
+    }
+
+    // Sampler uniforms can only be set on the current program, so it is swapped in temporarily
+    let mut previous_program: types::GLint = 0;
+    unsafe {
+        gl.GetIntegerv(CURRENT_PROGRAM, &mut previous_program as *mut i32);
+        gl.UseProgram(program_gl_handle);
+    }
+
+    let mut samplers = std::collections::HashMap::new();
+    let mut next_unit = 0;
+
+    for uniform in sampler_uniforms {
+        let units: Vec<i32> = (next_unit..next_unit + uniform.size).collect();
+        unsafe { gl.Uniform1iv(uniform.location, units.len() as i32, units.as_ptr()) };
+
+        samplers.insert(
+            uniform.name.clone(),
+            SamplerInfo {
+                name: uniform.name.clone(),
+                target: sampler_target(uniform.data_type).unwrap(), //unwrap since it was filtered on above
+                unit: next_unit as u32,
+                size: uniform.size,
+            },
+        );
+        next_unit += uniform.size;
+    }
+
+    unsafe { gl.UseProgram(previous_program as u32) };
+
+    samplers
+}
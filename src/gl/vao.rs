@@ -1,15 +1,24 @@
 use super::*;
 pub struct Vao {
     pub gl_handle: types::GLuint,
+    /// Index buffer attached to the Vao, if it is drawn with indices
+    element_buffer: Option<ElementBuffer>,
     /// Store a Rc to the Gl instance to ensure that we can destroy this resource when dropped
     gl: std::rc::Rc<Gl>,
 }
 
 impl Vao {
+    /// Creates a Vao from the given attribute pointers. All definitions are validated before any
+    /// GL state is touched, so an invalid one leaves nothing behind
     pub fn new(
         pointer_definitions: &Vec<VertexAttribPointerDefinition>,
         gl: std::rc::Rc<Gl>,
-    ) -> Vao {
+    ) -> Result<Vao, VaoError> {
+        let pointer_kinds = pointer_definitions
+            .iter()
+            .map(|def| attribute_pointer_kind(def.attribute_index, &def.attribute_definition))
+            .collect::<Result<Vec<_>, _>>()?;
+
         let mut gl_handle: types::GLuint = 0;
         unsafe {
             gl.GenVertexArrays(1, &mut gl_handle as *mut _);
@@ -19,7 +28,7 @@ impl Vao {
             gl.BindVertexArray(gl_handle);
         }
 
-        for def in pointer_definitions {
+        for (def, pointer_kind) in pointer_definitions.iter().zip(pointer_kinds) {
             let attr_def = &def.attribute_definition;
 
             def.vbo.bind(&gl);
@@ -27,11 +36,8 @@ impl Vao {
             unsafe {
                 gl.EnableVertexAttribArray(def.attribute_index);
 
-                match (
-                    def.attribute_definition.data_type,
-                    def.attribute_definition.normalized,
-                ) {
-                    (FLOAT, _) | (_, true) => {
+                match pointer_kind {
+                    AttribPointerKind::Float => {
                         gl.VertexAttribPointer(
                             def.attribute_index,
                             attr_def.dimension_count as i32,
@@ -41,7 +47,7 @@ impl Vao {
                             attr_def.offset as *const types::GLvoid,
                         );
                     }
-                    (UNSIGNED_SHORT, _) => {
+                    AttribPointerKind::Integer => {
                         gl.VertexAttribIPointer(
                             def.attribute_index,
                             attr_def.dimension_count as i32,
@@ -50,8 +56,11 @@ impl Vao {
                             attr_def.offset as *const types::GLvoid,
                         );
                     }
-                    _ => unimplemented!("unsupported vertex attribute type"),
                 };
+
+                if attr_def.divisor > 0 {
+                    gl.VertexAttribDivisor(def.attribute_index, attr_def.divisor);
+                }
             }
         }
         unsafe {
@@ -60,7 +69,128 @@ impl Vao {
 
         ArrayVbo::unbind(&gl);
 
-        Vao { gl_handle, gl }
+        Ok(Vao {
+            gl_handle,
+            element_buffer: None,
+            gl,
+        })
+    }
+    /// Creates a Vao reading every attribute of `V` from one interleaved buffer. Attribute
+    /// locations are looked up in the shader by field name. Fields the shader does not use are
+    /// skipped, but every attribute the shader does use has to be provided
+    pub fn from_vertex_buffer<V: Vertex>(
+        vbo: &ArrayVbo,
+        shader: &Shader,
+        gl: std::rc::Rc<Gl>,
+    ) -> Result<Vao, VaoError> {
+        let attributes = V::attributes();
+        validate_vertex_attributes(&attributes, shader)?;
+
+        let pointer_definitions =
+            vertex_pointer_definitions(vbo, &attributes, V::stride(), 0, shader);
+
+        Vao::new(&pointer_definitions, gl)
+    }
+    /// Like `from_vertex_buffer` but with a second buffer holding one `I` per instance, for use
+    /// with the instanced draw calls. Together the two types have to provide every attribute of
+    /// the shader
+    #[allow(dead_code)]
+    pub fn from_instanced_vertex_buffers<V: Vertex, I: Vertex>(
+        vertex_vbo: &ArrayVbo,
+        instance_vbo: &ArrayVbo,
+        shader: &Shader,
+        gl: std::rc::Rc<Gl>,
+    ) -> Result<Vao, VaoError> {
+        let vertex_attributes = V::attributes();
+        let instance_attributes = I::attributes();
+
+        let all_attributes: Vec<VertexAttribute> = vertex_attributes
+            .iter()
+            .chain(instance_attributes.iter())
+            .cloned()
+            .collect();
+        validate_vertex_attributes(&all_attributes, shader)?;
+
+        let mut pointer_definitions =
+            vertex_pointer_definitions(vertex_vbo, &vertex_attributes, V::stride(), 0, shader);
+        pointer_definitions.extend(vertex_pointer_definitions(
+            instance_vbo,
+            &instance_attributes,
+            I::stride(),
+            1,
+            shader,
+        ));
+
+        Vao::new(&pointer_definitions, gl)
+    }
+    /// Attaches an index buffer to the Vao, replacing any previous one
+    pub fn set_element_buffer(&mut self, element_buffer: ElementBuffer, gl: &super::Gl) {
+        self.bind(gl);
+        element_buffer.bind(gl);
+        Self::unbind(gl);
+        ElementBuffer::unbind(gl);
+
+        self.element_buffer = Some(element_buffer);
+    }
+    pub fn element_buffer(&self) -> Option<&ElementBuffer> {
+        self.element_buffer.as_ref()
+    }
+    /// Replaces the indices in the element buffer of the Vao
+    pub fn upload_indices<T: IndexType>(
+        &self,
+        usage: crate::gl::types::GLenum,
+        indices: &[T],
+        gl: &crate::gl::Gl,
+    ) -> Result<(), VaoError> {
+        let element_buffer = self
+            .element_buffer
+            .as_ref()
+            .ok_or(VaoError::NoElementBuffer)?;
+
+        self.bind(gl);
+        element_buffer.upload_indices(usage, indices, gl);
+        Self::unbind(gl);
+        Ok(())
+    }
+    /// Draws every index of the element buffer. The Vao must be bound
+    #[allow(dead_code)]
+    pub fn draw_elements(&self, mode: PrimitiveMode, gl: &super::Gl) -> Result<(), VaoError> {
+        let element_buffer = self
+            .element_buffer
+            .as_ref()
+            .ok_or(VaoError::NoElementBuffer)?;
+
+        draw_elements(
+            mode,
+            element_buffer.index_count() as i32,
+            element_buffer.index_type(),
+            0,
+            gl,
+        );
+        Ok(())
+    }
+    /// Draws every index of the element buffer `instance_count` times. The Vao must be bound
+    #[allow(dead_code)]
+    pub fn draw_elements_instanced(
+        &self,
+        mode: PrimitiveMode,
+        instance_count: i32,
+        gl: &super::Gl,
+    ) -> Result<(), VaoError> {
+        let element_buffer = self
+            .element_buffer
+            .as_ref()
+            .ok_or(VaoError::NoElementBuffer)?;
+
+        draw_elements_instanced(
+            mode,
+            element_buffer.index_count() as i32,
+            element_buffer.index_type(),
+            0,
+            instance_count,
+            gl,
+        );
+        Ok(())
     }
     pub fn bind(&self, gl: &super::Gl) {
         unsafe {
@@ -81,12 +211,258 @@ impl Drop for Vao {
         }
     }
 }
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum VaoError {
+    /// The shader reads an attribute that the vertex type does not have
+    MissingAttribute(String),
+    /// Float or normalized data fed to an integer attribute
+    AttributeTypeMismatch {
+        name: String,
+        data_type: types::GLenum,
+        shader_data_type: types::GLenum,
+    },
+    /// The data type cannot be used for vertex attributes
+    UnsupportedAttributeType {
+        attribute_index: u32,
+        data_type: types::GLenum,
+    },
+    /// Only unnormalized byte, short and int data can feed integer attributes
+    InvalidIntegerAttribute {
+        attribute_index: u32,
+        data_type: types::GLenum,
+    },
+    /// Attributes have 1 to 4 components, and packed types always have exactly 4
+    InvalidDimensionCount {
+        attribute_index: u32,
+        data_type: types::GLenum,
+        dimension_count: u8,
+    },
+    /// Tried to upload or draw indices on a Vao without an element buffer
+    NoElementBuffer,
+    /// Tried to replace the geometry of a Vao that draws with an element buffer by geometry
+    /// without indices
+    MissingIndices,
+    BaseVertexUnsupported(GlProfile),
+}
+
+impl std::error::Error for VaoError {}
+impl std::fmt::Display for VaoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let to_write = match self {
+            VaoError::MissingAttribute(name) => {
+                std::format!(
+                    "Shader attribute '{}' is not provided by the vertex type",
+                    name
+                )
+            }
+            VaoError::AttributeTypeMismatch {
+                name,
+                data_type,
+                shader_data_type,
+            } => std::format!(
+                "Vertex data of type 0x{:x} cannot feed shader attribute '{}' of type 0x{:x}",
+                data_type,
+                name,
+                shader_data_type
+            ),
+            VaoError::UnsupportedAttributeType {
+                attribute_index,
+                data_type,
+            } => std::format!(
+                "Type 0x{:x} of vertex attribute {} is not a vertex attribute type",
+                data_type,
+                attribute_index
+            ),
+            VaoError::InvalidIntegerAttribute {
+                attribute_index,
+                data_type,
+            } => std::format!(
+                "Vertex attribute {} of type 0x{:x} cannot be read as integers",
+                attribute_index,
+                data_type
+            ),
+            VaoError::InvalidDimensionCount {
+                attribute_index,
+                data_type,
+                dimension_count,
+            } => std::format!(
+                "Vertex attribute {} of type 0x{:x} cannot have {} components",
+                attribute_index,
+                data_type,
+                dimension_count
+            ),
+            VaoError::NoElementBuffer => String::from("Vao has no element buffer attached"),
+            VaoError::MissingIndices => {
+                String::from("Vao draws with an element buffer but no indices were given")
+            }
+            VaoError::BaseVertexUnsupported(profile) => std::format!(
+                "Drawing with a base vertex is not supported on the {:?} profile",
+                profile
+            ),
+        };
+        write!(f, "{}", to_write)
+    }
+}
+
+/// Which of the attribute pointer functions has to be used to set up an attribute
+enum AttribPointerKind {
+    /// VertexAttribPointer, the shader reads floats
+    Float,
+    /// VertexAttribIPointer, the shader reads integers
+    Integer,
+}
+
+fn attribute_pointer_kind(
+    attribute_index: u32,
+    def: &VertexAttributeDefinition,
+) -> Result<AttribPointerKind, VaoError> {
+    let is_packed = matches!(
+        def.data_type,
+        INT_2_10_10_10_REV | UNSIGNED_INT_2_10_10_10_REV
+    );
+
+    let valid_dimension_count = if is_packed {
+        def.dimension_count == 4
+    } else {
+        (1..=4).contains(&def.dimension_count)
+    };
+    if !valid_dimension_count {
+        return Err(VaoError::InvalidDimensionCount {
+            attribute_index,
+            data_type: def.data_type,
+            dimension_count: def.dimension_count,
+        });
+    }
+
+    if !matches!(
+        def.data_type,
+        FLOAT
+            | HALF_FLOAT
+            | INT_2_10_10_10_REV
+            | UNSIGNED_INT_2_10_10_10_REV
+            | BYTE
+            | UNSIGNED_BYTE
+            | SHORT
+            | UNSIGNED_SHORT
+            | INT
+            | UNSIGNED_INT
+    ) {
+        return Err(VaoError::UnsupportedAttributeType {
+            attribute_index,
+            data_type: def.data_type,
+        });
+    }
+
+    if !def.integer {
+        return Ok(AttribPointerKind::Float);
+    }
+    if !is_integer_vertex_data(def.data_type, def.normalized) {
+        return Err(VaoError::InvalidIntegerAttribute {
+            attribute_index,
+            data_type: def.data_type,
+        });
+    }
+    Ok(AttribPointerKind::Integer)
+}
+
+/// Checks that the attributes provide everything the shader reads, with matching types. Any data
+/// can be converted for float attributes, but integer attributes need integer data
+fn validate_vertex_attributes(
+    attributes: &[VertexAttribute],
+    shader: &Shader,
+) -> Result<(), VaoError> {
+    for shader_attribute in shader.attributes.values() {
+        let attribute = attributes
+            .iter()
+            .find(|a| a.name == shader_attribute.name)
+            .ok_or_else(|| VaoError::MissingAttribute(shader_attribute.name.clone()))?;
+
+        if is_integer_attribute_type(shader_attribute.data_type) && !attribute.is_integer() {
+            return Err(VaoError::AttributeTypeMismatch {
+                name: shader_attribute.name.clone(),
+                data_type: attribute.data_type,
+                shader_data_type: shader_attribute.data_type,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Builds the pointers for the attributes the shader uses. Matrix attributes take up one location
+/// per column
+fn vertex_pointer_definitions<'a>(
+    vbo: &'a ArrayVbo,
+    attributes: &[VertexAttribute],
+    stride: u32,
+    divisor: u32,
+    shader: &Shader,
+) -> Vec<VertexAttribPointerDefinition<'a>> {
+    let mut pointer_definitions = Vec::new();
+
+    for attribute in attributes {
+        let shader_attribute = match shader.attributes.get(attribute.name) {
+            Some(shader_attribute) => shader_attribute,
+            None => continue,
+        };
+        let location = shader_attribute.location as u32;
+
+        for column in 0..attribute.location_count as u32 {
+            pointer_definitions.push(VertexAttribPointerDefinition::new(
+                vbo,
+                location + column,
+                VertexAttributeDefinition {
+                    dimension_count: attribute.dimension_count,
+                    data_type: attribute.data_type,
+                    normalized: attribute.normalized,
+                    integer: is_integer_attribute_type(shader_attribute.data_type),
+                    stride,
+                    offset: attribute.offset + column as usize * attribute.location_size(),
+                    divisor,
+                },
+            ));
+        }
+    }
+
+    pointer_definitions
+}
+
+/// True if vertex data of the given type can feed integer attributes. It can also be converted
+/// to floats for float attributes
+pub fn is_integer_vertex_data(data_type: types::GLenum, normalized: bool) -> bool {
+    matches!(
+        data_type,
+        BYTE | UNSIGNED_BYTE | SHORT | UNSIGNED_SHORT | INT | UNSIGNED_INT
+    ) && !normalized
+}
+
+/// True for shader attribute types that have to be fed with VertexAttribIPointer
+pub fn is_integer_attribute_type(data_type: types::GLenum) -> bool {
+    matches!(
+        data_type,
+        INT | INT_VEC2
+            | INT_VEC3
+            | INT_VEC4
+            | UNSIGNED_INT
+            | UNSIGNED_INT_VEC2
+            | UNSIGNED_INT_VEC3
+            | UNSIGNED_INT_VEC4
+    )
+}
+
 pub struct VertexAttributeDefinition {
     pub dimension_count: u8,
     pub data_type: u32,
+    /// Only used when the data is converted to floats
     pub normalized: bool,
+    /// Feeds the data unconverted to an integer attribute (`int`, `uvec4`, ...) with
+    /// VertexAttribIPointer. The data has to be of an integer type and not normalized
+    pub integer: bool,
     pub stride: u32,
     pub offset: usize,
+    /// 0 to advance the attribute per vertex, otherwise it advances once every `divisor`
+    /// instances
+    pub divisor: u32,
 }
 pub struct VertexAttribPointerDefinition<'a> {
     vbo: &'a ArrayVbo,
@@ -1,7 +1,31 @@
 use super::*;
+use std::cell::Cell;
+
+/// How much storage a buffer allocates when data no longer fits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferGrowthPolicy {
+    /// Allocate exactly the size needed
+    Exact,
+    /// At least double the capacity, so that steadily growing data reallocates rarely
+    Double,
+}
+
+impl BufferGrowthPolicy {
+    pub fn grown_capacity(&self, capacity: usize, required: usize) -> usize {
+        match self {
+            BufferGrowthPolicy::Exact => required,
+            BufferGrowthPolicy::Double => required.max(capacity * 2),
+        }
+    }
+}
 
 pub struct ArrayVbo {
     gl_handle: u32,
+    /// Size in bytes of the storage currently allocated on the GPU
+    capacity: Cell<usize>,
+    /// Usage hint the storage was allocated with
+    usage: Cell<types::GLenum>,
+    growth_policy: BufferGrowthPolicy,
     /// Store a Rc to the Gl instance to ensure that we can destroy this resource when dropped
     gl: std::rc::Rc<Gl>,
 }
@@ -13,7 +37,24 @@ impl ArrayVbo {
             gl.GenBuffers(1, &mut gl_handle as *mut _);
         }
         assert!(gl_handle > 0);
-        Self { gl_handle, gl }
+        Self {
+            gl_handle,
+            capacity: Cell::new(0),
+            usage: Cell::new(STATIC_DRAW),
+            growth_policy: BufferGrowthPolicy::Double,
+            gl,
+        }
+    }
+    pub fn with_growth_policy(mut self, growth_policy: BufferGrowthPolicy) -> Self {
+        self.growth_policy = growth_policy;
+        self
+    }
+    pub fn capacity(&self) -> usize {
+        self.capacity.get()
+    }
+    #[allow(dead_code)]
+    pub fn usage(&self) -> types::GLenum {
+        self.usage.get()
     }
 
     pub fn bind(&self, gl: &super::Gl) {
@@ -26,6 +67,14 @@ impl ArrayVbo {
             gl.BindBuffer(super::ARRAY_BUFFER, 0);
         }
     }
+    /// Exposes the buffer to shader storage blocks bound to the given binding point. Only
+    /// available on the Core43 profile
+    #[allow(dead_code)]
+    pub fn bind_to_storage_binding_point(&self, binding_point: u32, gl: &super::Gl) {
+        unsafe {
+            gl.BindBufferBase(super::SHADER_STORAGE_BUFFER, binding_point, self.gl_handle);
+        }
+    }
     pub fn upload_array_vbo_vec<T>(
         &self,
         usage: crate::gl::types::GLenum,
@@ -42,21 +91,118 @@ impl ArrayVbo {
             self.upload_array_vbo_raw(usage, bytes, gl)
         }
     }
+    /// Replaces the content of the buffer. The existing storage is reused if the data fits and
+    /// the usage matches, otherwise new storage is allocated following the growth policy
     pub fn upload_array_vbo_raw(
         &self,
         usage: crate::gl::types::GLenum,
         bytes: &[u8],
         gl: &crate::gl::Gl,
     ) {
+        if let Some(capacity) = self.reallocation_capacity(usage, bytes.len()) {
+            self.allocate(usage, capacity, gl);
+        }
+
+        // The storage was just made large enough
+        self.write(0, bytes, gl);
+    }
+    /// Makes sure the buffer can hold at least `capacity` bytes without reallocating. Growing the
+    /// storage discards the current content
+    pub fn reserve(&self, usage: crate::gl::types::GLenum, capacity: usize, gl: &crate::gl::Gl) {
+        if let Some(capacity) = self.reallocation_capacity(usage, capacity) {
+            self.allocate(usage, capacity, gl);
+        }
+    }
+    /// Overwrites part of the buffer starting at element `first_element`. The range has to lie
+    /// within the capacity of the buffer
+    #[allow(dead_code)]
+    pub fn update_array_vbo_slice<T>(
+        &self,
+        first_element: usize,
+        data: &[T],
+        gl: &crate::gl::Gl,
+    ) -> Result<(), BufferError> {
+        let element_size = std::mem::size_of::<T>().max(1);
+        if (first_element + data.len()) * element_size > self.capacity.get() {
+            return Err(BufferError::OutOfRange {
+                first: first_element,
+                count: data.len(),
+                len: self.capacity.get() / element_size,
+            });
+        }
+
+        unsafe {
+            let bytes =
+                std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data));
+
+            self.write(first_element * std::mem::size_of::<T>(), bytes, gl);
+        }
+        Ok(())
+    }
+    /// Overwrites part of the buffer without reallocating it. The byte range has to lie within the
+    /// capacity of the buffer
+    pub fn update_array_vbo_raw(
+        &self,
+        offset: usize,
+        bytes: &[u8],
+        gl: &crate::gl::Gl,
+    ) -> Result<(), BufferError> {
+        if offset + bytes.len() > self.capacity.get() {
+            return Err(BufferError::OutOfRange {
+                first: offset,
+                count: bytes.len(),
+                len: self.capacity.get(),
+            });
+        }
+
+        self.write(offset, bytes, gl);
+        Ok(())
+    }
+    /// Hands the current storage back to the driver and allocates fresh storage of the same size.
+    /// Lets the CPU write new data while the GPU is still reading the old, instead of waiting
+    pub fn orphan(&self, gl: &crate::gl::Gl) {
+        self.allocate(self.usage.get(), self.capacity.get(), gl);
+    }
+    /// The capacity to reallocate with before holding `required` bytes with `usage`, or None if
+    /// the current storage can be reused
+    fn reallocation_capacity(
+        &self,
+        usage: crate::gl::types::GLenum,
+        required: usize,
+    ) -> Option<usize> {
+        let capacity = self.capacity.get();
+        if required > capacity {
+            Some(self.growth_policy.grown_capacity(capacity, required))
+        } else if usage != self.usage.get() {
+            // Only the usage changed, growing here would double the buffer on every switch
+            Some(capacity)
+        } else {
+            None
+        }
+    }
+    fn write(&self, offset: usize, bytes: &[u8], gl: &crate::gl::Gl) {
         self.bind(gl);
         unsafe {
-            gl.BufferData(
+            gl.BufferSubData(
                 crate::gl::ARRAY_BUFFER,
+                offset as isize,
                 bytes.len() as isize,
                 bytes.as_ptr() as *const std::ffi::c_void,
+            );
+        }
+    }
+    fn allocate(&self, usage: crate::gl::types::GLenum, capacity: usize, gl: &crate::gl::Gl) {
+        self.bind(gl);
+        unsafe {
+            gl.BufferData(
+                crate::gl::ARRAY_BUFFER,
+                capacity as isize,
+                std::ptr::null(),
                 usage,
             );
         }
+        self.capacity.set(capacity);
+        self.usage.set(usage);
     }
 }
 
@@ -0,0 +1,154 @@
+use super::*;
+
+/// A Rust type that can be read by a vertex attribute, along with how GL should interpret it
+pub trait VertexAttributeType {
+    const DIMENSION_COUNT: u8;
+    const DATA_TYPE: types::GLenum;
+    const NORMALIZED: bool;
+    /// Number of consecutive attribute locations the type takes up. Matrices take one per column
+    const LOCATION_COUNT: u8 = 1;
+}
+
+macro_rules! impl_vertex_attribute_type {
+    ($t:ty, $dimension_count:expr, $data_type:expr, $normalized:expr) => {
+        impl VertexAttributeType for $t {
+            const DIMENSION_COUNT: u8 = $dimension_count;
+            const DATA_TYPE: types::GLenum = $data_type;
+            const NORMALIZED: bool = $normalized;
+        }
+    };
+}
+
+impl_vertex_attribute_type!(f32, 1, FLOAT, false);
+impl_vertex_attribute_type!(glm::Vec2, 2, FLOAT, false);
+impl_vertex_attribute_type!(glm::Vec3, 3, FLOAT, false);
+impl_vertex_attribute_type!(glm::Vec4, 4, FLOAT, false);
+impl_vertex_attribute_type!([f32; 2], 2, FLOAT, false);
+impl_vertex_attribute_type!([f32; 3], 3, FLOAT, false);
+impl_vertex_attribute_type!([f32; 4], 4, FLOAT, false);
+impl_vertex_attribute_type!(i32, 1, INT, false);
+impl_vertex_attribute_type!(glm::IVec2, 2, INT, false);
+impl_vertex_attribute_type!(glm::IVec3, 3, INT, false);
+impl_vertex_attribute_type!(glm::IVec4, 4, INT, false);
+impl_vertex_attribute_type!(u32, 1, UNSIGNED_INT, false);
+impl_vertex_attribute_type!(glm::UVec2, 2, UNSIGNED_INT, false);
+impl_vertex_attribute_type!(glm::UVec3, 3, UNSIGNED_INT, false);
+impl_vertex_attribute_type!(glm::UVec4, 4, UNSIGNED_INT, false);
+impl_vertex_attribute_type!(i16, 1, SHORT, false);
+impl_vertex_attribute_type!([i16; 2], 2, SHORT, false);
+impl_vertex_attribute_type!([i16; 3], 3, SHORT, false);
+impl_vertex_attribute_type!([i16; 4], 4, SHORT, false);
+impl_vertex_attribute_type!(u16, 1, UNSIGNED_SHORT, false);
+impl_vertex_attribute_type!([u16; 2], 2, UNSIGNED_SHORT, false);
+impl_vertex_attribute_type!([u16; 3], 3, UNSIGNED_SHORT, false);
+impl_vertex_attribute_type!([u16; 4], 4, UNSIGNED_SHORT, false);
+impl_vertex_attribute_type!(i8, 1, BYTE, false);
+impl_vertex_attribute_type!([i8; 4], 4, BYTE, false);
+impl_vertex_attribute_type!(u8, 1, UNSIGNED_BYTE, false);
+impl_vertex_attribute_type!([u8; 4], 4, UNSIGNED_BYTE, false);
+// Colors reach the shader as normalized vec4s
+impl_vertex_attribute_type!(Color, 4, UNSIGNED_BYTE, true);
+
+macro_rules! impl_vertex_attribute_type_matrix {
+    ($t:ty, $column_count:expr, $row_count:expr) => {
+        impl VertexAttributeType for $t {
+            const DIMENSION_COUNT: u8 = $row_count;
+            const DATA_TYPE: types::GLenum = FLOAT;
+            const NORMALIZED: bool = false;
+            const LOCATION_COUNT: u8 = $column_count;
+        }
+    };
+}
+
+// Mostly useful as per-instance transforms
+impl_vertex_attribute_type_matrix!(glm::Mat2, 2, 2);
+impl_vertex_attribute_type_matrix!(glm::Mat3, 3, 3);
+impl_vertex_attribute_type_matrix!(glm::Mat4, 4, 4);
+
+/// One attribute of an interleaved vertex struct
+#[derive(Debug, Clone)]
+pub struct VertexAttribute {
+    /// Name of the shader attribute the field feeds
+    pub name: &'static str,
+    pub dimension_count: u8,
+    pub data_type: types::GLenum,
+    pub normalized: bool,
+    /// Byte offset of the field within the vertex
+    pub offset: usize,
+    /// Number of consecutive attribute locations the field takes up
+    pub location_count: u8,
+}
+
+impl VertexAttribute {
+    pub fn new<T: VertexAttributeType>(name: &'static str, offset: usize) -> Self {
+        Self {
+            name,
+            dimension_count: T::DIMENSION_COUNT,
+            data_type: T::DATA_TYPE,
+            normalized: T::NORMALIZED,
+            offset,
+            location_count: T::LOCATION_COUNT,
+        }
+    }
+    /// Like `new` but infers the attribute type from an accessor of the field. Used by
+    /// `impl_vertex!`
+    pub fn of_field<V, T: VertexAttributeType, F: Fn(&V) -> &T>(
+        name: &'static str,
+        offset: usize,
+        _field: F,
+    ) -> Self {
+        Self::new::<T>(name, offset)
+    }
+    /// Size in bytes of the data read from a single location
+    pub fn location_size(&self) -> usize {
+        let component_size = match self.data_type {
+            BYTE | UNSIGNED_BYTE => 1,
+            SHORT | UNSIGNED_SHORT | HALF_FLOAT => 2,
+            // Packed types hold all components in 4 bytes
+            INT_2_10_10_10_REV | UNSIGNED_INT_2_10_10_10_REV => return 4,
+            _ => 4,
+        };
+        self.dimension_count as usize * component_size
+    }
+    /// True if the data can feed integer attributes, see `is_integer_vertex_data`
+    pub fn is_integer(&self) -> bool {
+        is_integer_vertex_data(self.data_type, self.normalized)
+    }
+}
+
+/// A struct holding all attributes of one vertex, laid out interleaved in a single buffer. Usually
+/// implemented through `impl_vertex!`
+pub trait Vertex: Sized {
+    fn attributes() -> Vec<VertexAttribute>;
+    fn stride() -> u32 {
+        std::mem::size_of::<Self>() as u32
+    }
+}
+
+/// Implements `Vertex` for a struct, feeding each listed field to the shader attribute of the
+/// same name. The struct has to be `#[repr(C)]` so that its layout matches what GL reads
+///
+/// ```ignore
+/// #[repr(C)]
+/// struct SpriteVertex {
+///     position: glm::Vec2,
+///     color: gl::Color,
+/// }
+/// impl_vertex!(SpriteVertex { position, color });
+/// ```
+#[macro_export]
+macro_rules! impl_vertex {
+    ($t:ty { $($field:ident),* $(,)? }) => {
+        impl $crate::gl::Vertex for $t {
+            fn attributes() -> Vec<$crate::gl::VertexAttribute> {
+                vec![$(
+                    $crate::gl::VertexAttribute::of_field(
+                        stringify!($field),
+                        std::mem::offset_of!($t, $field),
+                        |vertex: &$t| &vertex.$field,
+                    )
+                ),*]
+            }
+        }
+    };
+}
@@ -19,9 +19,8 @@ pub struct Scene {
 
     // GL resources
     shader: gl::Shader,
-    vao: gl::Vao,
-    triangle_positions_vbo: gl::ArrayVbo,
-    triangle_colors_vbo: gl::ArrayVbo,
+    camera_ubo: gl::UniformBuffer,
+    triangle_mesh: gl::Mesh,
 }
 
 impl Scene {
@@ -34,37 +33,16 @@ impl Scene {
             100.0,
         ));
 
-        // One VBO for the position attribute, one for the colors
-        let triangle_positions_vbo = gl::ArrayVbo::new(gl.clone());
-        let triangle_colors_vbo = gl::ArrayVbo::new(gl.clone());
-
-        let vao = gl::Vao::new(
-            &vec![
-                gl::VertexAttribPointerDefinition::new(
-                    &triangle_positions_vbo,
-                    0,
-                    gl::VertexAttributeDefinition {
-                        dimension_count: 3,
-                        data_type: gl::FLOAT,
-                        normalized: false,
-                        stride: 0,
-                        offset: 0,
-                    },
-                ),
-                gl::VertexAttribPointerDefinition::new(
-                    &triangle_colors_vbo,
-                    1,
-                    gl::VertexAttributeDefinition {
-                        dimension_count: 4,
-                        data_type: gl::UNSIGNED_BYTE,
-                        normalized: true,
-                        stride: 0,
-                        offset: 0,
-                    },
-                ),
-            ],
-            gl.clone(),
-        );
+        let shader = gl::new_default_shader(gl.clone());
+
+        // The triangle is uploaded once and moved around by its model matrix
+        //unwrap since the default shader only reads attributes that every mesh has
+        let triangle_mesh =
+            gl::Mesh::new(&gl::MeshData::ngon(3, 60.0), &shader, gl.clone()).unwrap();
+
+        // Camera data lives in a uniform buffer that every shader reads from
+        let camera_ubo = gl::UniformBuffer::new(gl.clone());
+        camera_ubo.bind_to_binding_point(gl::DEFAULT_CAMERA_BINDING_POINT, &gl);
 
         let mut res = Self {
             camera,
@@ -83,16 +61,30 @@ impl Scene {
             },
             triangle_rotation: 0.0,
             rotational_speed: 0.0,
-            shader: gl::new_default_shader(gl),
-            vao,
-            triangle_positions_vbo,
-            triangle_colors_vbo,
+            shader,
+            camera_ubo,
+            triangle_mesh,
         };
 
+        //unwrap since the built-in shader source declares the camera block
+        res.on_shader_reloaded(&gl).unwrap();
         res.randomize();
 
         res
     }
+    /// Gives access to the shader so that it can be swapped out when hot-reloading
+    pub fn shader_mut(&mut self) -> &mut gl::Shader {
+        &mut self.shader
+    }
+    /// Connects the shader to the uniform buffers. Has to be redone whenever the shader program
+    /// is replaced
+    pub fn on_shader_reloaded(&self, gl: &gl::Gl) -> Result<(), gl::ShaderError> {
+        self.shader.bind_uniform_block(
+            gl::DEFAULT_CAMERA_BLOCK,
+            gl::DEFAULT_CAMERA_BINDING_POINT,
+            gl,
+        )
+    }
     pub fn resize_view(&mut self, size: glm::UVec2) {
         if let Some(orth) = self.camera.projection.as_orthographic_mut() {
             orth.size = glm::convert(size);
@@ -126,58 +118,40 @@ impl Scene {
 
         // Bind gl entities
         self.shader.bind(gl);
-        self.vao.bind(gl);
 
-        // Prepare geometry
+        // Place the triangle on its orbit around the center
         let distance_to_center = 200.0;
         let triangle_position =
             glm::rotate_vec2(&glm::vec2(distance_to_center, 0.0), self.triangle_rotation);
-        let offset = glm::vec3(triangle_position.x, triangle_position.y, 0.0);
         let z = 0.5;
-        let triangle_size_factor = 30.0;
-
-        // 2D model space
-        let top_p = glm::vec2(0.0, -2.0) * triangle_size_factor;
-        let bottom_right_p = glm::vec2(1.5, 1.0) * triangle_size_factor;
-        let bottom_left_p = glm::vec2(-1.5, 1.0) * triangle_size_factor;
-
-        // 3D world space
-        let top_p = glm::vec3(top_p.x, top_p.y, z) + offset;
-        let bottom_right_p = glm::vec3(bottom_right_p.x, bottom_right_p.y, z) + offset;
-        let bottom_left_p = glm::vec3(bottom_left_p.x, bottom_left_p.y, z) + offset;
-        let positions = vec![top_p, bottom_right_p, bottom_left_p];
-
-        let colors = vec![
-            self.triangle_color,
-            self.triangle_color,
-            self.triangle_color,
-        ];
-
-        // Upload geometry
-        self.triangle_positions_vbo
-            .upload_array_vbo_vec(gl::STREAM_DRAW, &positions, gl);
-        self.triangle_colors_vbo
-            .upload_array_vbo_vec(gl::STREAM_DRAW, &colors, gl);
-
-        // Set projection
-        let vp_mat = self.camera.view_projection_matrix();
-        let projection_matrix_location =
-            gl::get_uniform_location(&self.shader, gl::DEFAULT_PROJECTION_UNIFORM, gl).unwrap(); //unwrap since we are using hard coded name
-
-        unsafe {
-            gl.UniformMatrix4fv(
-                projection_matrix_location,
-                1,
-                crate::gl::FALSE,
-                vp_mat.as_ptr(),
-            );
-        }
+        let model = glm::translation(&glm::vec3(triangle_position.x, triangle_position.y, z));
+
+        self.set_optional_uniform(gl::DEFAULT_MODEL_UNIFORM, &model, gl);
+        self.set_optional_uniform(gl::DEFAULT_TINT_UNIFORM, &self.triangle_color, gl);
+
+        // Upload camera data, shared by every shader through the uniform buffer
+        self.camera_ubo
+            .upload_std140(gl::STREAM_DRAW, &gl::CameraUniforms::from(&self.camera), gl);
 
         // Render
-        gl::draw_arrays(positions.len() as i32, gl);
+        self.triangle_mesh.draw(gl);
 
         // Clean up
         gl::Shader::unbind(gl);
-        gl::Vao::unbind(gl);
+    }
+    /// Sets a uniform if the shader uses it. A hot-reloaded shader may not, in which case the GL
+    /// compiler has stripped it and there is nothing to set
+    fn set_optional_uniform<T: gl::UniformValue + ?Sized>(
+        &self,
+        name: &str,
+        value: &T,
+        gl: &gl::Gl,
+    ) {
+        if !self.shader.uniforms.contains_key(name) {
+            return;
+        }
+        if let Err(e) = self.shader.set_uniform(name, value, gl) {
+            eprintln!("Could not set uniform of the scene shader: {}", e);
+        }
     }
 }
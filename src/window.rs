@@ -1,16 +1,16 @@
 use anyhow::anyhow;
 pub struct GlWindow {
     pub gl: std::rc::Rc<crate::gl::Gl>,
-    pub gl_context: sdl2::video::GLContext,
+    /// The profile of the context that was actually created
+    #[allow(dead_code)]
+    pub profile: GlProfile,
+    pub _gl_context: sdl2::video::GLContext,
     pub window: sdl2::video::Window,
-    pub video: sdl2::VideoSubsystem,
+    pub _video: sdl2::VideoSubsystem,
     pub sdl: sdl2::Sdl,
 }
 
-pub enum GlProfile {
-    _Core43,
-    ES3,
-}
+pub use crate::gl::GlProfile;
 
 impl GlWindow {
     pub fn new(title: &str, size: glm::UVec2, profile: GlProfile) -> anyhow::Result<Self> {
@@ -21,7 +21,7 @@ impl GlWindow {
         let video = sdl.video().map_err(|e| anyhow!(e))?;
 
         let context_params = match profile {
-            GlProfile::_Core43 => (sdl2::video::GLProfile::Core, 4, 3),
+            GlProfile::Core43 => (sdl2::video::GLProfile::Core, 4, 3),
             GlProfile::ES3 => (sdl2::video::GLProfile::GLES, 3, 0),
         };
 
@@ -49,11 +49,14 @@ impl GlWindow {
 
         window.set_size(size.x, size.y)?;
 
+        let profile = GlProfile::detect(&gl);
+
         Ok(Self {
+            profile,
             sdl,
-            video,
+            _video: video,
             window,
-            gl_context,
+            _gl_context: gl_context,
             gl,
         })
     }